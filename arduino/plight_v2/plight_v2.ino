@@ -0,0 +1,213 @@
+// Framed v2 protocol, see src/core/framed_protocol.rs
+// Packet: 'P' 'L' | version | kind | length hi | length lo | payload | crc hi | crc lo
+// RLE frame payload: run | r | g | b per run
+// Delta frame payload: start hi | start lo | count | count * (r | g | b) per changed span
+
+//----------------------Settings-----------------------
+#define NUM_LEDS 81
+#define DI_PIN 13
+#define CURRENT_LIMIT 2000
+
+#define START_FLASHES 1
+//----------------------Setting-----------------------
+
+#define serialRate 115200
+#define PROTOCOL_VERSION 2
+#define READ_TIMEOUT 100
+
+#define KIND_HELLO 0x01
+#define KIND_HELLO_REPLY 0x02
+#define KIND_ACK 0x06
+#define KIND_FRAME 0x10
+#define KIND_RLE_FRAME 0x11
+#define KIND_DELTA_FRAME 0x12
+#define KIND_NACK 0x15
+
+#define NACK_CRC 1
+#define NACK_LENGTH 2
+#define NACK_KIND 3
+#define NACK_VERSION 4
+
+#include <FastLED.h>
+CRGB leds[NUM_LEDS];
+// Compressed frames are only accepted when smaller than a full one
+uint8_t payload[NUM_LEDS * 3];
+
+uint16_t crc16Update(uint16_t crc, uint8_t data) {
+  crc ^= (uint16_t)data << 8;
+  for (uint8_t b = 0; b < 8; b++) {
+    crc = (crc & 0x8000) ? (crc << 1) ^ 0x1021 : crc << 1;
+  }
+  return crc;
+}
+
+uint16_t crc16(uint16_t crc, const uint8_t *data, uint16_t length) {
+  for (uint16_t i = 0; i < length; i++) crc = crc16Update(crc, data[i]);
+  return crc;
+}
+
+bool readBytes(uint8_t *buf, uint16_t length) {
+  return Serial.readBytes(buf, length) == length;
+}
+
+void sendPacket(uint8_t kind, const uint8_t *payload, uint16_t length) {
+  uint8_t header[] = {PROTOCOL_VERSION, kind, (uint8_t)(length >> 8), (uint8_t)length};
+  uint16_t crc = crc16(0xFFFF, header, sizeof header);
+  crc = crc16(crc, payload, length);
+
+  Serial.write('P');
+  Serial.write('L');
+  Serial.write(header, sizeof header);
+  Serial.write(payload, length);
+  Serial.write((uint8_t)(crc >> 8));
+  Serial.write((uint8_t)crc);
+}
+
+void nack(uint8_t reason) {
+  sendPacket(KIND_NACK, &reason, 1);
+}
+
+// Drops a payload that can not be stored, together with its CRC
+void skip(uint16_t length) {
+  uint8_t b;
+  for (uint32_t i = 0; i < (uint32_t)length + 2; i++) {
+    if (!readBytes(&b, 1)) return;
+  }
+}
+
+// Both decoders check the whole payload before touching the LEDs
+bool decodeRle(uint16_t length, bool apply) {
+  if (length % 4 != 0) return false;
+  uint16_t led = 0;
+  for (uint16_t i = 0; i < length; i += 4) {
+    uint8_t run = payload[i];
+    if (run == 0 || led + run > NUM_LEDS) return false;
+    for (uint8_t j = 0; j < run; j++, led++) {
+      if (!apply) continue;
+      leds[led].r = payload[i + 1];
+      leds[led].g = payload[i + 2];
+      leds[led].b = payload[i + 3];
+    }
+  }
+  return led == NUM_LEDS;
+}
+
+bool decodeDelta(uint16_t length, bool apply) {
+  uint16_t i = 0;
+  while (i < length) {
+    if (i + 3 > length) return false;
+    uint16_t start = ((uint16_t)payload[i] << 8) | payload[i + 1];
+    uint8_t count = payload[i + 2];
+    i += 3;
+    if (start + count > NUM_LEDS || i + count * 3 > length) return false;
+    if (apply) memcpy(&leds[start], &payload[i], count * 3);
+    i += count * 3;
+  }
+  return true;
+}
+
+void setup()
+{
+  FastLED.addLeds<WS2811, DI_PIN, RGB>(leds, NUM_LEDS);
+  if (CURRENT_LIMIT > 0) FastLED.setMaxPowerInVoltsAndMilliamps(5, CURRENT_LIMIT);
+
+  if (START_FLASHES) {
+    LEDS.showColor(CRGB(41, 21, 6));
+  }
+
+  Serial.begin(serialRate);
+  Serial.setTimeout(READ_TIMEOUT);
+  Serial.print("Ada\n");
+}
+
+void loop() {
+  uint8_t b;
+  if (!readBytes(&b, 1) || b != 'P') return;
+  if (!readBytes(&b, 1) || b != 'L') return;
+
+  uint8_t header[4];
+  if (!readBytes(header, sizeof header)) return;
+  uint8_t version = header[0], kind = header[1];
+  uint16_t length = ((uint16_t)header[2] << 8) | header[3];
+  uint16_t crc = crc16(0xFFFF, header, sizeof header);
+
+  if (version != PROTOCOL_VERSION) {
+    skip(length);
+    nack(NACK_VERSION);
+    return;
+  }
+
+  if (kind == KIND_HELLO) {
+    uint8_t received[2];
+    if (length != 0) {
+      skip(length);
+      nack(NACK_LENGTH);
+      return;
+    }
+    if (!readBytes(received, 2)) return;
+    if (((uint16_t)received[0] << 8 | received[1]) != crc) {
+      nack(NACK_CRC);
+      return;
+    }
+    uint8_t payload[] = {(uint8_t)(NUM_LEDS >> 8), (uint8_t)NUM_LEDS};
+    sendPacket(KIND_HELLO_REPLY, payload, sizeof payload);
+    return;
+  }
+
+  if (kind == KIND_FRAME) {
+    if (length != NUM_LEDS * 3) {
+      skip(length);
+      nack(NACK_LENGTH);
+      return;
+    }
+
+    // Colors go straight into the buffer, it is only shown once the CRC matches
+    uint8_t *data = (uint8_t *)leds;
+    if (!readBytes(data, length)) return;
+    crc = crc16(crc, data, length);
+
+    uint8_t received[2];
+    if (!readBytes(received, 2)) return;
+    if (((uint16_t)received[0] << 8 | received[1]) != crc) {
+      nack(NACK_CRC);
+      return;
+    }
+
+    FastLED.show();
+    sendPacket(KIND_ACK, NULL, 0);
+    return;
+  }
+
+  if (kind == KIND_RLE_FRAME || kind == KIND_DELTA_FRAME) {
+    if (length > sizeof payload) {
+      skip(length);
+      nack(NACK_LENGTH);
+      return;
+    }
+
+    if (!readBytes(payload, length)) return;
+    crc = crc16(crc, payload, length);
+
+    uint8_t received[2];
+    if (!readBytes(received, 2)) return;
+    if (((uint16_t)received[0] << 8 | received[1]) != crc) {
+      nack(NACK_CRC);
+      return;
+    }
+
+    bool rle = kind == KIND_RLE_FRAME;
+    if (!(rle ? decodeRle(length, false) : decodeDelta(length, false))) {
+      nack(NACK_LENGTH);
+      return;
+    }
+    if (rle) decodeRle(length, true);
+    else decodeDelta(length, true);
+
+    FastLED.show();
+    sendPacket(KIND_ACK, NULL, 0);
+    return;
+  }
+
+  skip(length);
+  nack(NACK_KIND);
+}
@@ -0,0 +1,130 @@
+//! Compares image sampling paths on a 4K frame. Run with `cargo bench --bench parse_image`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use image::{ImageBuffer, Rgb, RgbImage};
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use plight::config::ParseMode;
+use plight::core::layout::{Corner, Direction, Layout, LayoutConf, Rect};
+use plight::core::led_color::LedColor;
+use plight::utils::color_math::average;
+use plight::utils::image_processing::{downscale, sample_colors};
+
+const ITERATIONS: u32 = 20;
+
+/// The previous implementation, copying the frame into an `Array2` and every rectangle into
+/// a `Vec`
+fn ndarray_copy(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, rects: &[Option<Rect>]) -> Vec<LedColor> {
+    let (width_p, height_p) = (img.width() as usize, img.height() as usize);
+    let pixels: Array2<Rgb<u8>> = Array2::from_shape_fn((height_p, width_p), |(y, x)| {
+        *img.get_pixel(x as u32, y as u32)
+    });
+
+    rects
+        .iter()
+        .map(|rect| match rect {
+            Some(rect) => {
+                let slice = pixels.slice(s![rect.y.0..rect.y.1, rect.x.0..rect.x.1]);
+                average(&slice.iter().copied().collect::<Vec<_>>())
+            }
+            None => LedColor::default(),
+        })
+        .collect()
+}
+
+fn bench(name: &str, mut run: impl FnMut() -> Vec<LedColor>) -> Vec<LedColor> {
+    let mut colors = run();
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let started = Instant::now();
+        colors = black_box(run());
+        total += started.elapsed();
+    }
+    println!(
+        "{:<24} {:>8.2} ms",
+        name,
+        total.as_secs_f64() * 1000.0 / ITERATIONS as f64
+    );
+    colors
+}
+
+/// Largest channel difference between two results
+fn max_difference(a: &[LedColor], b: &[LedColor]) -> f32 {
+    a.iter()
+        .zip(b)
+        .flat_map(|(a, b)| (0..3).map(move |i| (a.channels()[i] - b.channels()[i]).abs()))
+        .fold(0.0, f32::max)
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let img: RgbImage = ImageBuffer::from_fn(3840, 2160, |_, _| Rgb(rng.gen()));
+
+    let conf = LayoutConf {
+        file: None,
+        start_corner: Corner::BottomLeft,
+        direction: Direction::CounterClockwise,
+        top: None,
+        right: None,
+        bottom: None,
+        left: None,
+        gaps: vec![],
+        offset: None,
+        skipped: vec![],
+    };
+    let layout = Layout::new(&conf, 29, 15, 7).unwrap();
+    let area = Rect::full(img.width() as usize, img.height() as usize);
+    let rects = layout.sample_rects(area, 0, 200);
+
+    let reference = bench("ndarray copy", || ndarray_copy(&img, &rects));
+
+    let direct = bench("direct", || {
+        sample_colors(&img, &rects, &ParseMode::Average, 1)
+    });
+    assert_eq!(
+        direct, reference,
+        "direct sampling must match the previous implementation"
+    );
+
+    for step in [2, 4, 8] {
+        let colors = bench(&format!("direct, step {}", step), || {
+            sample_colors(&img, &rects, &ParseMode::Average, step)
+        });
+        println!(
+            "{:<24} {:>8.2} max difference",
+            "",
+            max_difference(&colors, &reference)
+        );
+    }
+
+    for factor in [2, 4] {
+        let small_rects = layout.sample_rects(
+            Rect::full(
+                img.width() as usize / factor,
+                img.height() as usize / factor,
+            ),
+            0,
+            200 / factor,
+        );
+        let colors = bench(&format!("downscale {}", factor), || {
+            sample_colors(
+                &downscale(&img, factor),
+                &small_rects,
+                &ParseMode::Average,
+                1,
+            )
+        });
+        println!(
+            "{:<24} {:>8.2} max difference",
+            "",
+            max_difference(&colors, &reference)
+        );
+    }
+
+    bench("direct, median", || {
+        sample_colors(&img, &rects, &ParseMode::Median, 1)
+    });
+}
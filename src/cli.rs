@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+/// Command line options, everything else lives in the config file
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Args {
+    /// Record every frame sent to the strip (`.gif` file or PNG directory)
+    pub record: Option<PathBuf>,
+    /// Record the LED sequence produced by the source for later replay
+    pub record_frames: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => match args.next() {
+                    Some(path) => parsed.record = Some(path.into()),
+                    None => bail!("--record needs a path"),
+                },
+                "--record-frames" => match args.next() {
+                    Some(path) => parsed.record_frames = Some(path.into()),
+                    None => bail!("--record-frames needs a path"),
+                },
+                _ => bail!("Unknown argument {}", arg),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
@@ -6,15 +6,33 @@ use std::path::PathBuf;
 
 use confique::{toml::FormatOptions, Config};
 use lazy_static::lazy_static;
+use log::warn;
 use serde::Deserialize;
 use serde::Serialize;
 
 use anyhow::Result;
 
+use crate::core::arduino_strip::ArduinoStripConf;
+use crate::core::ddp_strip::DdpStripConf;
+use crate::core::dmx::DmxStripConf;
+use crate::core::hyperion_strip::HyperionStripConf;
+use crate::core::layout::{Layout, LayoutConf};
+use crate::core::multi_strip::OutputConf;
+use crate::core::openrgb_strip::OpenRgbStripConf;
+use crate::core::strip::StripMod;
+use crate::core::wled_strip::WledStripConf;
 use crate::modes::behaviors::audio::AudioBhvConf;
 use crate::modes::behaviors::solid::SolidBhvConf;
 use crate::modes::behaviors::BehaviorMod;
+use crate::modes::filters::blur::BlurFltConf;
+use crate::modes::filters::brightness_cap::BrightnessCapFltConf;
+use crate::modes::filters::mirror::MirrorFltConf;
+use crate::modes::filters::rotate::RotateFltConf;
+use crate::modes::filters::saturation_boost::SaturationBoostFltConf;
+use crate::modes::filters::smoothing::SmoothingFltConf;
+use crate::modes::filters::FilterMod;
 use crate::modes::sources::color::ColorSrcConf;
+use crate::modes::sources::replay::ReplaySrcConf;
 use crate::modes::sources::screen::ScreenSrcConf;
 use crate::modes::sources::wallpaper::WallpaperSrcConf;
 use crate::modes::sources::SourceMod;
@@ -38,6 +56,10 @@ pub struct Conf {
     #[config(nested)]
     pub source: SourceConf,
 
+    // Filters applied between the source and the behavior
+    #[config(nested)]
+    pub filter: FilterConf,
+
     // Several PLight behavior configuration
     #[config(nested)]
     pub behavior: BehaviorConf,
@@ -47,7 +69,10 @@ impl Conf {
     pub fn new() -> Result<Conf> {
         let default_config_path = get_default_config_path()?;
         create_new_config(&default_config_path)?;
-        Ok(Conf::from_file(default_config_path)?)
+
+        let mut conf = Conf::from_file(default_config_path)?;
+        conf.strip.migrate_legacy_keys();
+        Ok(conf)
     }
 }
 
@@ -73,6 +98,28 @@ fn create_new_config(default_config_path: &str) -> Result<()> {
 pub struct GlobalConf {
     #[config(default = "Average")]
     pub parse_mode: ParseMode,
+
+    /// Sample every n-th pixel of every n-th row when parsing images (1 samples them all).
+    /// 2 - 4 cuts the work on 4K screens with barely visible difference
+    #[config(default = 1)]
+    pub sample_step: usize,
+
+    /// Box downscale images by this factor before sampling (1 keeps the full resolution).
+    /// It reads the whole frame, so it only pays off when the LEDs sample most of it
+    #[config(default = 1)]
+    pub downscale: usize,
+
+    /// Target frames per second of the poll loop (0 for unlimited)
+    #[config(default = 60.0)]
+    pub fps: f64,
+
+    /// Do not send a frame to the strip if it is identical to the previous one
+    #[config(default = true)]
+    pub skip_unchanged: bool,
+
+    /// Resend an unchanged frame after this many milliseconds anyway
+    #[config(default = 1000)]
+    pub keepalive_ms: u64,
 }
 
 #[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
@@ -83,6 +130,10 @@ pub enum ParseMode {
 
 #[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
 pub struct StripConf {
+    /// Strip backend (Arduino, Wled, Sacn, ArtNet, Ddp, OpenRgb, Hyperion, Terminal, Multi)
+    #[config(default = "Arduino")]
+    pub mode: StripMod,
+
     #[config(default = 29)]
     pub width: usize,
     #[config(default = 15)]
@@ -95,25 +146,53 @@ pub struct StripConf {
     #[config(default = 200)]
     pub thickness_p: usize,
 
-    // #[config(default = false)]
-    // pub clockwise: bool,
-    #[config(default = "/dev/ttyUSB0")]
-    pub serial_port: String,
-    #[config(default = 115200)]
-    pub baudrate: u32,
+    /// Placement of the LEDs around the screen
+    #[config(nested)]
+    pub layout: LayoutConf,
 
     /// Tint configuration
     #[config(nested)]
     pub tint: TintConf,
+
+    /// Physical strips used by the Multi backend, each gets its own segment of the sequence
+    #[config(default = [])]
+    pub outputs: Vec<OutputConf>,
+
+    /// Deprecated, moved to `[strip.arduino]` and used there when set
+    pub serial_port: Option<String>,
+    /// Deprecated, moved to `[strip.arduino]` and used there when set
+    pub baudrate: Option<u32>,
+
+    #[config(nested)]
+    pub arduino: ArduinoStripConf,
+    #[config(nested)]
+    pub wled: WledStripConf,
+    #[config(nested)]
+    pub dmx: DmxStripConf,
+    #[config(nested)]
+    pub ddp: DdpStripConf,
+    #[config(nested)]
+    pub openrgb: OpenRgbStripConf,
+    #[config(nested)]
+    pub hyperion: HyperionStripConf,
 }
 
 impl StripConf {
-    pub fn len(&self) -> usize {
-        self.width * 2 + self.height * 2 - self.bottom_gap
+    pub fn layout(&self) -> Result<Layout> {
+        Layout::new(&self.layout, self.width, self.height, self.bottom_gap)
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.width == 0 || self.height == 0 || self.width * 2 + self.height * 2 == self.bottom_gap
+    /// Configs written before the `[strip.arduino]` section keep the port settings directly
+    /// under `[strip]`, they override the new section so old setups keep working
+    fn migrate_legacy_keys(&mut self) {
+        if let Some(serial_port) = self.serial_port.take() {
+            warn!("[strip] serial_port is deprecated, move it to [strip.arduino]");
+            self.arduino.serial_port = serial_port;
+        }
+        if let Some(baudrate) = self.baudrate.take() {
+            warn!("[strip] baudrate is deprecated, move it to [strip.arduino]");
+            self.arduino.baudrate = baudrate;
+        }
     }
 }
 
@@ -129,9 +208,22 @@ pub struct TintConf {
     pub brightness: [f32; 3],
 }
 
+#[cfg(test)]
+impl TintConf {
+    /// Tint that leaves colors untouched
+    pub fn identity() -> Self {
+        TintConf {
+            order: "RGB".into(),
+            gamma: [1.0; 3],
+            saturation: [1.0; 3],
+            brightness: [1.0; 3],
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
 pub struct SourceConf {
-    /// Colors' source mode (Color, Screen, Wallpaper)
+    /// Colors' source mode (Color, Screen, Wallpaper, Replay)
     #[config(default = "Color")]
     pub mode: SourceMod,
 
@@ -141,6 +233,28 @@ pub struct SourceConf {
     pub screen: ScreenSrcConf,
     #[config(nested)]
     pub wallpaper: WallpaperSrcConf,
+    #[config(nested)]
+    pub replay: ReplaySrcConf,
+}
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct FilterConf {
+    /// Filters to apply, in order (Smoothing, BrightnessCap, SaturationBoost, Mirror, Rotate, Blur)
+    #[config(default = [])]
+    pub chain: Vec<FilterMod>,
+
+    #[config(nested)]
+    pub smoothing: SmoothingFltConf,
+    #[config(nested)]
+    pub brightness_cap: BrightnessCapFltConf,
+    #[config(nested)]
+    pub saturation_boost: SaturationBoostFltConf,
+    #[config(nested)]
+    pub mirror: MirrorFltConf,
+    #[config(nested)]
+    pub rotate: RotateFltConf,
+    #[config(nested)]
+    pub blur: BlurFltConf,
 }
 
 #[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
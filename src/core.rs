@@ -1,31 +1,176 @@
+#[cfg(test)]
+mod arduino_emulator;
 pub mod arduino_strip;
+pub mod artnet_strip;
+pub mod ddp_strip;
+pub mod dedup_strip;
+pub mod dmx;
+pub mod frame_pacer;
+pub mod frame_record;
+pub mod framed_protocol;
+pub mod hyperion_strip;
+pub mod layout;
 pub mod led_color;
 pub mod led_sequence;
+pub mod multi_strip;
+pub mod openrgb_strip;
+pub mod recording_strip;
+pub mod sacn_strip;
+pub mod stage_timer;
 pub mod strip;
+pub mod terminal_strip;
+pub mod wled_strip;
 
-use anyhow::Result;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
 use log::info;
 
 use crate::config::CONFIG;
 use crate::modes::behaviors::BehaviorMod;
+use crate::modes::filters::FilterChain;
 use crate::modes::sources::SourceMod;
+use crate::utils::latest::{latest_channel, LatestReceiver, LatestSender};
+use dedup_strip::DedupStrip;
+use frame_pacer::FramePacer;
+use frame_record::FrameRecorder;
+use layout::Layout;
 use led_sequence::LedSequence;
+use stage_timer::StageTimer;
 use strip::Strip;
 
-pub fn poll(strip: Box<dyn Strip>, source_mod: SourceMod, behavior_mod: BehaviorMod) -> Result<()> {
-    let mut led_sequence = LedSequence::new(CONFIG.strip.len());
+/// Set once a shutdown was requested, the capture stage stops before its next frame
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Asks [`poll`] to wind down. The stages stop in order and drop the behavior with its strip,
+/// so strips can restore the terminal or finish their files. A second request exits at once,
+/// in case a stage is stuck on a device.
+pub fn stop() {
+    if STOPPING.swap(true, Ordering::Relaxed) {
+        process::exit(130);
+    }
+    info!("Stopping, repeat to exit immediately");
+}
+
+fn stopping() -> bool {
+    STOPPING.load(Ordering::Relaxed)
+}
 
+/// Runs capture, processing and output on their own threads. Stages hand frames over
+/// through single-slot channels, so capturing the next frame overlaps with sending the
+/// current one and a slow stage skips stale frames instead of queueing them.
+pub fn poll(
+    strip: Box<dyn Strip>,
+    layout: Layout,
+    source_mod: SourceMod,
+    behavior_mod: BehaviorMod,
+    recorder: Option<FrameRecorder>,
+) -> Result<()> {
     // Test set_leds
+    let led_sequence = LedSequence::new(layout.len());
     let _ = strip.set_leds(&led_sequence);
-    while strip.set_leds(&led_sequence).is_err() {}
+    while strip.set_leds(&led_sequence).is_err() {
+        if stopping() {
+            return Ok(());
+        }
+    }
 
     info!("Strip tests passed");
 
-    let mut source = source_mod.get_source()?;
-    let mut behavior = behavior_mod.get_behavior(strip)?;
+    let strip: Box<dyn Strip> = if CONFIG.global.skip_unchanged {
+        Box::new(DedupStrip::new(
+            strip,
+            Duration::from_millis(CONFIG.global.keepalive_ms),
+        ))
+    } else {
+        strip
+    };
+
+    let (captured_tx, captured_rx) = latest_channel();
+    let (processed_tx, processed_rx) = latest_channel();
+
+    let capture = thread::spawn(move || capture_stage(source_mod, layout, recorder, captured_tx));
+    let process = thread::spawn(move || process_stage(captured_rx, processed_tx));
+
+    let output = output_stage(strip, behavior_mod, processed_rx);
+
+    // Output has dropped its receiver by now, which stops the other stages
+    let process = process
+        .join()
+        .map_err(|_| anyhow!("process stage panicked"))?;
+    let capture = capture
+        .join()
+        .map_err(|_| anyhow!("capture stage panicked"))?;
+
+    capture?;
+    process?;
+    output
+}
+
+fn capture_stage(
+    source_mod: SourceMod,
+    layout: Layout,
+    mut recorder: Option<FrameRecorder>,
+    tx: LatestSender<LedSequence>,
+) -> Result<()> {
+    let mut led_sequence = LedSequence::new(layout.len());
+    let mut source = source_mod.get_source(&layout)?;
+    let mut pacer = FramePacer::new(CONFIG.global.fps);
+    let mut timer = StageTimer::new("Capture");
 
-    loop {
+    while !stopping() {
+        let started = Instant::now();
         source.poll_next(&mut led_sequence)?;
+        timer.record(started);
+
+        // Recorded before the hand-over, where a slow stage would skip frames. Recordings keep
+        // the unfiltered frames, so replaying them runs the filters again
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&led_sequence)?;
+        }
+
+        if !tx.send(led_sequence.clone()) {
+            break;
+        }
+        pacer.wait();
+    }
+
+    Ok(())
+}
+
+fn process_stage(rx: LatestReceiver<LedSequence>, tx: LatestSender<LedSequence>) -> Result<()> {
+    let mut filters = FilterChain::new(&CONFIG.filter.chain)?;
+    let mut timer = StageTimer::new("Process");
+
+    while let Some(mut led_sequence) = rx.recv() {
+        let started = Instant::now();
+        filters.apply(&mut led_sequence)?;
+        timer.record(started);
+
+        if !tx.send(led_sequence) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn output_stage(
+    strip: Box<dyn Strip>,
+    behavior_mod: BehaviorMod,
+    rx: LatestReceiver<LedSequence>,
+) -> Result<()> {
+    let mut behavior = behavior_mod.get_behavior(strip)?;
+    let mut timer = StageTimer::new("Output");
+
+    while let Some(led_sequence) = rx.recv() {
+        let started = Instant::now();
         behavior.poll_next(&led_sequence)?;
+        timer.record(started);
     }
+
+    Ok(())
 }
@@ -0,0 +1,161 @@
+//! Software copy of `arduino/arduino.ino` sitting on a pseudo-terminal pair, so the serial
+//! protocol of [`ArduinoStrip`](crate::core::arduino_strip::ArduinoStrip) can be tested
+//! without a board by pointing `serial_port` at [`ArduinoEmulator::port_name`].
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Context, Result};
+use log::error;
+use serialport::{SerialPort, TTYPort};
+
+const PREFIX: [u8; 3] = [89, 124, 234];
+const GREETING: &[u8] = b"Ada\n";
+
+/// Misbehavior injected into the emulated firmware
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EmulatorFault {
+    #[default]
+    None,
+    /// Frames are shown but never answered
+    NoReply,
+    /// Frames are answered with a postfix that does not match the prefix
+    WrongPostfix,
+}
+
+#[derive(Default)]
+struct EmulatorState {
+    leds: Mutex<Vec<[u8; 3]>>,
+    fault: Mutex<EmulatorFault>,
+    frames: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+pub struct ArduinoEmulator {
+    port_name: String,
+    state: Arc<EmulatorState>,
+    thread: Option<JoinHandle<()>>,
+    // Keeps the slave side open, otherwise the master reads fail until someone opens it
+    _slave: TTYPort,
+}
+
+impl ArduinoEmulator {
+    pub fn spawn(num_leds: usize) -> Result<Self> {
+        let (mut master, slave) = TTYPort::pair()?;
+        let port_name = slave
+            .name()
+            .context("Pseudo-terminal has no slave device name")?;
+
+        let state = Arc::new(EmulatorState {
+            leds: Mutex::new(vec![[0; 3]; num_leds]),
+            ..Default::default()
+        });
+
+        master.write_all(GREETING)?;
+
+        let thread_state = state.clone();
+        let thread = thread::spawn(move || {
+            if let Err(e) = run_firmware(&mut master, &thread_state, num_leds) {
+                error!("Arduino emulator error: {}", e);
+            }
+        });
+
+        Ok(ArduinoEmulator {
+            port_name,
+            state,
+            thread: Some(thread),
+            _slave: slave,
+        })
+    }
+
+    /// Device path to open as the serial port
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    /// Colors of the last shown frame, in the order they were received
+    pub fn leds(&self) -> Vec<[u8; 3]> {
+        self.state.leds.lock().unwrap().clone()
+    }
+
+    /// Number of frames shown so far
+    pub fn frames(&self) -> usize {
+        self.state.frames.load(Ordering::SeqCst)
+    }
+
+    pub fn set_fault(&self, fault: EmulatorFault) {
+        *self.state.fault.lock().unwrap() = fault;
+    }
+}
+
+impl Drop for ArduinoEmulator {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Mirrors `loop()` of the sketch, including the way it falls back to waiting for the first
+/// prefix byte after any mismatch
+fn run_firmware(port: &mut TTYPort, state: &EmulatorState, num_leds: usize) -> io::Result<()> {
+    let read_byte = |port: &mut TTYPort| -> io::Result<Option<u8>> {
+        let mut byte = [0; 1];
+        loop {
+            if state.stopped.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            match port.read(&mut byte) {
+                Ok(1) => return Ok(Some(byte[0])),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+        }
+    };
+
+    'frame: loop {
+        let mut i = 0;
+        while i < PREFIX.len() {
+            let Some(byte) = read_byte(port)? else {
+                return Ok(());
+            };
+            i = if byte == PREFIX[i] { i + 1 } else { 0 };
+        }
+
+        let mut header = [0; 3];
+        for byte in &mut header {
+            let Some(value) = read_byte(port)? else {
+                return Ok(());
+            };
+            *byte = value;
+        }
+        let [hi, lo, chk] = header;
+        if chk != hi ^ lo ^ 0x55 {
+            continue 'frame;
+        }
+
+        let mut leds = vec![[0; 3]; num_leds];
+        for led in &mut leds {
+            for channel in led.iter_mut() {
+                let Some(value) = read_byte(port)? else {
+                    return Ok(());
+                };
+                *channel = value;
+            }
+        }
+        *state.leds.lock().unwrap() = leds;
+        state.frames.fetch_add(1, Ordering::SeqCst);
+
+        let fault = *state.fault.lock().unwrap();
+        match fault {
+            EmulatorFault::None => port.write_all(&[PREFIX[2], PREFIX[1], PREFIX[0]])?,
+            EmulatorFault::NoReply => {}
+            EmulatorFault::WrongPostfix => port.write_all(&PREFIX)?,
+        }
+        port.flush()?;
+    }
+}
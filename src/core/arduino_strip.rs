@@ -1,40 +1,226 @@
+use std::io;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use log::{error, warn};
+use confique::Config;
+use log::{debug, error, info, warn};
 use rand::random;
+use serde::{Deserialize, Serialize};
 use serialport::{self, SerialPort};
 
-use crate::config::CONFIG;
+use crate::config::TintConf;
+use crate::core::framed_protocol::{self, negotiate, FrameEncoder};
 use crate::core::led_sequence::LedSequence;
 use crate::core::strip::Strip;
-use crate::errors::PLightError::{PostfixReading, WrongLength};
+use crate::errors::PLightError::{
+    self, FrameRejected, LedCountMismatch, SerialDeviceNotFound, UnsupportedProtocolVersion,
+    WrongLength,
+};
+use crate::utils::serial::{usb_ports, wait_for_greeting, UsbFilter};
 
 const PREFIX: [u8; 3] = [89, 124, 234];
+const ADALIGHT_PREFIX: [u8; 3] = *b"Ada";
+const GREETING: &[u8] = b"Ada\n";
+const AUTO_PORT: &str = "auto";
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct ArduinoStripConf {
+    /// Serial device path or "auto" to search for the board answering with the "Ada" greeting.
+    /// With USB filters set, the path is only used when the device matches them
+    #[config(default = "/dev/ttyUSB0")]
+    pub serial_port: String,
+    #[config(default = 115200)]
+    pub baudrate: u32,
+
+    /// Wire protocol (PLight, PLightV2, Adalight)
+    #[config(default = "PLight")]
+    pub protocol: SerialProtocol,
+
+    /// Send only changed or run-length encoded LEDs (PLightV2 only)
+    #[config(default = false)]
+    pub compression: bool,
+    /// Number of compressed frames between two full frames
+    #[config(default = 60)]
+    pub keyframe_interval: u32,
+
+    /// Only search USB devices with this vendor id
+    pub usb_vid: Option<u16>,
+    /// Only search USB devices with this product id
+    pub usb_pid: Option<u16>,
+    /// Only search USB devices with this serial number
+    pub usb_serial_number: Option<String>,
+
+    /// How long to wait for the greeting after opening a port (milliseconds)
+    #[config(default = 3000)]
+    pub greeting_timeout_ms: u64,
+
+    /// Delay before the first reconnection attempt (milliseconds)
+    #[config(default = 500)]
+    pub reconnect_delay_ms: u64,
+    /// The delay doubles after every failed attempt up to this value (milliseconds)
+    #[config(default = 8000)]
+    pub max_reconnect_delay_ms: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum SerialProtocol {
+    /// Protocol of the bundled sketch, the board answers every frame with the reversed prefix
+    PLight,
+    /// Versioned framed protocol of the `plight_v2` sketch with CRC and acknowledgements
+    PLightV2,
+    /// Stock Adalight header with the LED count, understood by Adalight, Prismatik and
+    /// HyperSerial firmware
+    Adalight,
+}
+
+struct Connection {
+    port: Option<Box<dyn SerialPort + Send>>,
+    encoder: FrameEncoder,
+    retry_delay: Duration,
+    next_retry: Instant,
+}
+
+impl Connection {
+    fn port(
+        &mut self,
+        conf: &ArduinoStripConf,
+        strip_length: usize,
+    ) -> Result<Option<&mut Box<dyn SerialPort + Send>>> {
+        if self.port.is_none() && Instant::now() >= self.next_retry {
+            match connect(conf, strip_length) {
+                Ok(port) => {
+                    info!("Serial port reconnected");
+                    self.port = Some(port);
+                    self.retry_delay = Duration::from_millis(conf.reconnect_delay_ms);
+                }
+                Err(e) if is_config_error(&e) => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Serial port reconnection failed: {}, next attempt in {:?}",
+                        e, self.retry_delay
+                    );
+                    self.schedule_retry(conf);
+                }
+            }
+        }
+
+        Ok(self.port.as_mut())
+    }
+
+    fn disconnect(&mut self, conf: &ArduinoStripConf) {
+        self.port = None;
+        self.encoder.reset();
+        self.retry_delay = Duration::from_millis(conf.reconnect_delay_ms);
+        self.next_retry = Instant::now() + self.retry_delay;
+    }
+
+    fn schedule_retry(&mut self, conf: &ArduinoStripConf) {
+        self.next_retry = Instant::now() + self.retry_delay;
+        self.retry_delay = (self.retry_delay * 2).min(Duration::from_millis(
+            conf.max_reconnect_delay_ms.max(conf.reconnect_delay_ms),
+        ));
+    }
+}
 
 #[derive(Clone)]
 pub struct ArduinoStrip {
-    port: Arc<Mutex<Box<dyn SerialPort + Send>>>,
+    conf: ArduinoStripConf,
+    connection: Arc<Mutex<Connection>>,
+    tint: TintConf,
     strip_length: usize,
 }
 
-impl Strip for ArduinoStrip {
-    fn new() -> Result<Self>
-    where
-        Self: Sized,
-    {
-        let port: Box<dyn SerialPort + Send> =
-            serialport::new(CONFIG.strip.serial_port.clone(), CONFIG.strip.baudrate)
-                .timeout(Duration::from_millis(1000))
-                .open()?;
+impl ArduinoStrip {
+    pub fn new(conf: &ArduinoStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let mut connection = Connection {
+            port: None,
+            encoder: FrameEncoder::new(conf.compression, conf.keyframe_interval),
+            retry_delay: Duration::from_millis(conf.reconnect_delay_ms),
+            next_retry: Instant::now(),
+        };
+
+        // A missing device is not fatal, it will be picked up as soon as it appears
+        match connect(conf, strip_length) {
+            Ok(port) => connection.port = Some(port),
+            Err(e) if is_config_error(&e) => return Err(e),
+            Err(e) => {
+                warn!("Serial port is not available yet: {}", e);
+                connection.schedule_retry(conf);
+            }
+        }
 
         Ok(ArduinoStrip {
-            port: Arc::new(Mutex::new(port)),
-            strip_length: CONFIG.strip.len(),
+            conf: conf.clone(),
+            connection: Arc::new(Mutex::new(connection)),
+            tint: tint.clone(),
+            strip_length,
         })
     }
 
+    fn tinted_colors(&self, led_colors: &LedSequence) -> Vec<u8> {
+        led_colors
+            .into_iter()
+            .flat_map(|led_color| led_color.apply_tint(&self.tint))
+            .collect()
+    }
+
+    fn encode_frame(&self, prefix: [u8; 3], hi: u8, lo: u8, led_colors: &LedSequence) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(6 + self.strip_length * 3);
+        frame.extend_from_slice(&prefix);
+        frame.extend_from_slice(&[hi, lo, hi ^ lo ^ 0x55]);
+        frame.extend_from_slice(&self.tinted_colors(led_colors));
+        frame
+    }
+
+    fn send_frame(
+        &self,
+        port: &mut Box<dyn SerialPort + Send>,
+        encoder: &mut FrameEncoder,
+        led_colors: &LedSequence,
+    ) -> Result<()> {
+        let _ = port.clear(serialport::ClearBuffer::Input);
+
+        match self.conf.protocol {
+            SerialProtocol::PLight => {
+                port.write_all(&self.encode_frame(PREFIX, random(), random(), led_colors))?;
+                port.flush()?;
+            }
+            SerialProtocol::PLightV2 => {
+                return framed_protocol::send_frame(port, encoder, &self.tinted_colors(led_colors));
+            }
+            SerialProtocol::Adalight => {
+                let [hi, lo] = (self.strip_length.saturating_sub(1) as u16).to_be_bytes();
+                port.write_all(&self.encode_frame(ADALIGHT_PREFIX, hi, lo, led_colors))?;
+                port.flush()?;
+
+                // Adalight firmware does not answer
+                return Ok(());
+            }
+        }
+
+        let mut buf = [0; 3];
+        match port.read_exact(&mut buf) {
+            Ok(_) => {
+                buf.reverse();
+                if buf != PREFIX {
+                    warn!("Wrong postfix {:?}, expected {:?}", buf, PREFIX);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::TimedOut {
+                    warn!("Timeout reading postfix - data may have been sent successfully");
+                    Ok(())
+                } else {
+                    Err(e.into())
+                }
+            }
+        }
+    }
+}
+
+impl Strip for ArduinoStrip {
     fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
         if led_colors.len() != self.strip_length {
             return Err(WrongLength {
@@ -44,48 +230,191 @@ impl Strip for ArduinoStrip {
             .into());
         }
 
-        let mut port = match self.port.lock() {
-            Ok(port) => port,
+        let mut connection = match self.connection.lock() {
+            Ok(connection) => connection,
             Err(e) => {
                 error!("Serial port error: {}", e);
                 return Ok(());
             }
         };
 
-        let _ = port.clear(serialport::ClearBuffer::Input);
+        // Frames are dropped while the device is away
+        if connection.port(&self.conf, self.strip_length)?.is_none() {
+            return Ok(());
+        }
+
+        let Connection { port, encoder, .. } = &mut *connection;
+        let Some(port) = port else {
+            return Ok(());
+        };
 
-        port.write_all(&PREFIX)?;
+        match self.send_frame(port, encoder, led_colors) {
+            Ok(()) => Ok(()),
+            // The encoder was reset, so the next frame goes out in full
+            Err(e) if matches!(e.downcast_ref(), Some(FrameRejected { .. })) => {
+                warn!("Frame dropped: {}", e);
+                Ok(())
+            }
+            // The device is gone or out of sync, start over with a fresh negotiation
+            Err(e) => {
+                warn!("Serial port lost: {}", e);
+                connection.disconnect(&self.conf);
+                Ok(())
+            }
+        }
+    }
+}
 
-        let hi: u8 = random();
-        let lo: u8 = random();
-        let chk = hi ^ lo ^ 0x55;
+fn connect(conf: &ArduinoStripConf, strip_length: usize) -> Result<Box<dyn SerialPort + Send>> {
+    let mut port = open_port(conf)?;
+    if conf.protocol == SerialProtocol::PLightV2 {
+        negotiate(&mut port, strip_length)?;
+    }
+    Ok(port)
+}
 
-        port.write_all(&[hi, lo, chk])?;
+/// A firmware with another protocol version or LED count will not fix itself by retrying
+fn is_config_error(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<PLightError>(),
+        Some(LedCountMismatch { .. } | UnsupportedProtocolVersion { .. })
+    )
+}
 
-        for led_color in led_colors {
-            let color_bytes = led_color.apply_tint();
-            port.write_all(&color_bytes)?;
+/// Opens the configured port, or the first candidate that greets when searching. Boards reset
+/// on open, so waiting for the greeting also keeps the first frames from being lost.
+fn open_port(conf: &ArduinoStripConf) -> Result<Box<dyn SerialPort + Send>> {
+    let greeting_timeout = Duration::from_millis(conf.greeting_timeout_ms);
+    let filter = UsbFilter {
+        vid: conf.usb_vid,
+        pid: conf.usb_pid,
+        serial_number: conf.usb_serial_number.as_deref(),
+    };
+
+    if conf.serial_port != AUTO_PORT && filter.is_empty() {
+        let mut port = open_path(&conf.serial_port, conf.baudrate)?;
+        if !wait_for_greeting(&mut port, GREETING, greeting_timeout)? {
+            warn!(
+                "No greeting from {}, sending frames anyway",
+                conf.serial_port
+            );
         }
+        return Ok(port);
+    }
 
-        port.flush()?;
+    // With a filter the fixed path is only tried first, provided the device matches
+    let mut candidates = usb_ports(&filter)?;
+    if conf.serial_port != AUTO_PORT {
+        match candidates.iter().position(|path| *path == conf.serial_port) {
+            Some(i) => candidates[..=i].rotate_right(1),
+            None => warn!(
+                "{} does not match the USB filter, searching matching devices instead",
+                conf.serial_port
+            ),
+        }
+    }
 
-        let mut buf = [0; 3];
-        match port.read_exact(&mut buf) {
-            Ok(_) => {
-                buf.reverse();
-                if buf != PREFIX {
-                    warn!("Wrong postfix {:?}, expected {:?}", buf, PREFIX);
-                }
-                Ok(())
-            }
+    for path in candidates {
+        let mut port = match open_path(&path, conf.baudrate) {
+            Ok(port) => port,
             Err(e) => {
-                if e.kind() == std::io::ErrorKind::TimedOut {
-                    warn!("Timeout reading postfix - data may have been sent successfully");
-                    Ok(())
-                } else {
-                    Err(PostfixReading(e).into())
-                }
+                debug!("Skipping serial port {}: {}", path, e);
+                continue;
+            }
+        };
+
+        match wait_for_greeting(&mut port, GREETING, greeting_timeout) {
+            Ok(true) => {
+                info!("Found strip on {}", path);
+                return Ok(port);
             }
+            Ok(false) => debug!("No greeting from {}", path),
+            Err(e) => debug!("Skipping serial port {}: {}", path, e),
         }
     }
+
+    Err(SerialDeviceNotFound.into())
+}
+
+fn open_path(path: &str, baudrate: u32) -> Result<Box<dyn SerialPort + Send>> {
+    let port: Box<dyn SerialPort + Send> = serialport::new(path, baudrate)
+        .timeout(Duration::from_millis(1000))
+        .open()?;
+
+    Ok(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arduino_emulator::{ArduinoEmulator, EmulatorFault};
+    use crate::core::led_color::LedColor;
+
+    const COLORS: [[u8; 3]; 3] = [[255, 0, 0], [0, 128, 0], [1, 2, 3]];
+
+    fn strip(emulator: &ArduinoEmulator) -> ArduinoStrip {
+        let conf = ArduinoStripConf {
+            serial_port: emulator.port_name().into(),
+            baudrate: 115200,
+            protocol: SerialProtocol::PLight,
+            compression: false,
+            keyframe_interval: 60,
+            usb_vid: None,
+            usb_pid: None,
+            usb_serial_number: None,
+            greeting_timeout_ms: 1000,
+            reconnect_delay_ms: 500,
+            max_reconnect_delay_ms: 8000,
+        };
+        ArduinoStrip::new(&conf, &TintConf::identity(), COLORS.len()).unwrap()
+    }
+
+    fn sequence(colors: &[[u8; 3]]) -> LedSequence {
+        colors.iter().map(|&rgb| LedColor::from(rgb)).collect()
+    }
+
+    #[test]
+    fn frame_reaches_the_board() {
+        let emulator = ArduinoEmulator::spawn(COLORS.len()).unwrap();
+        let strip = strip(&emulator);
+
+        strip.set_leds(&sequence(&COLORS)).unwrap();
+        assert_eq!(emulator.leds(), COLORS);
+        assert_eq!(emulator.frames(), 1);
+
+        strip.set_leds(&sequence(&[[9; 3]; 3])).unwrap();
+        assert_eq!(emulator.leds(), [[9; 3]; 3]);
+        assert_eq!(emulator.frames(), 2);
+    }
+
+    #[test]
+    fn missing_reply_is_not_an_error() {
+        let emulator = ArduinoEmulator::spawn(COLORS.len()).unwrap();
+        let strip = strip(&emulator);
+
+        emulator.set_fault(EmulatorFault::NoReply);
+        strip.set_leds(&sequence(&COLORS)).unwrap();
+        assert_eq!(emulator.frames(), 1);
+
+        emulator.set_fault(EmulatorFault::None);
+        strip.set_leds(&sequence(&[[9; 3]; 3])).unwrap();
+        assert_eq!(emulator.leds(), [[9; 3]; 3]);
+        assert_eq!(emulator.frames(), 2);
+    }
+
+    #[test]
+    fn wrong_postfix_is_not_an_error() {
+        let emulator = ArduinoEmulator::spawn(COLORS.len()).unwrap();
+        let strip = strip(&emulator);
+
+        emulator.set_fault(EmulatorFault::WrongPostfix);
+        strip.set_leds(&sequence(&COLORS)).unwrap();
+        assert_eq!(emulator.leds(), COLORS);
+        assert_eq!(emulator.frames(), 1);
+
+        emulator.set_fault(EmulatorFault::None);
+        strip.set_leds(&sequence(&[[9; 3]; 3])).unwrap();
+        assert_eq!(emulator.leds(), [[9; 3]; 3]);
+        assert_eq!(emulator.frames(), 2);
+    }
 }
@@ -0,0 +1,118 @@
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use anyhow::Result;
+
+use crate::config::TintConf;
+use crate::core::dmx::{split_into_universes, DmxStripConf};
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+
+const ARTNET_PORT: u16 = 6454;
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+
+pub struct ArtNetStrip {
+    socket: UdpSocket,
+    universe: u16,
+    channel_offset: usize,
+    tint: TintConf,
+    strip_length: usize,
+    sequence: AtomicU8,
+}
+
+impl ArtNetStrip {
+    pub fn new(conf: &DmxStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let host = conf.host.clone().unwrap_or("255.255.255.255".into());
+
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.connect((host.as_str(), ARTNET_PORT))?;
+
+        Ok(ArtNetStrip {
+            socket,
+            universe: conf.universe,
+            channel_offset: conf.channel_offset,
+            tint: tint.clone(),
+            strip_length,
+            sequence: AtomicU8::new(1),
+        })
+    }
+}
+
+impl Strip for ArtNetStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        let universes = split_into_universes(
+            led_colors,
+            &self.tint,
+            self.strip_length,
+            self.universe,
+            self.channel_offset,
+        )?;
+
+        // Zero disables sequencing on the receiver, so it is skipped on wrap
+        let sequence = match self.sequence.fetch_add(1, Ordering::Relaxed) {
+            0 => self.sequence.fetch_add(1, Ordering::Relaxed),
+            sequence => sequence,
+        };
+
+        for (universe, data) in universes {
+            self.socket
+                .send(&encode_packet(sequence, universe, &data))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds an ArtDmx packet, `universe` is the 15 bit port-address
+pub fn encode_packet(sequence: u8, universe: u16, data: &[u8]) -> Vec<u8> {
+    // The length must be even and at least 2
+    let length = (data.len() + data.len() % 2).max(2);
+
+    let mut packet = Vec::with_capacity(18 + length);
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.extend_from_slice(&(universe & 0x7fff).to_le_bytes());
+    packet.extend_from_slice(&(length as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet.resize(18 + length, 0);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dmx_packet_layout() {
+        let packet = encode_packet(5, 0x8123, &[1, 2, 3]);
+
+        assert_eq!(&packet[..8], ARTNET_ID);
+        assert_eq!(u16::from_le_bytes([packet[8], packet[9]]), OP_DMX);
+        assert_eq!(
+            u16::from_be_bytes([packet[10], packet[11]]),
+            PROTOCOL_VERSION
+        );
+        assert_eq!(packet[12], 5);
+        assert_eq!(packet[13], 0);
+        // Only 15 bits of port-address
+        assert_eq!(u16::from_le_bytes([packet[14], packet[15]]), 0x0123);
+        // Padded to an even length
+        assert_eq!(u16::from_be_bytes([packet[16], packet[17]]), 4);
+        assert_eq!(packet[18..], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn empty_data_still_has_two_channels() {
+        let packet = encode_packet(1, 0, &[]);
+
+        assert_eq!(u16::from_be_bytes([packet[16], packet[17]]), 2);
+        assert_eq!(packet[18..], [0, 0]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::TintConf;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+use crate::errors::PLightError::WrongLength;
+
+const DDP_VERSION: u8 = 0x40;
+const DDP_PUSH: u8 = 0x01;
+/// RGB with 8 bits per channel
+const DDP_TYPE_RGB8: u8 = 0x0b;
+/// Largest payload that keeps packets within a standard MTU (480 pixels)
+const DDP_MAX_DATA: usize = 1440;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct DdpStripConf {
+    /// Address of the DDP controller
+    #[config(default = "4.3.2.1")]
+    pub host: String,
+    #[config(default = 4048)]
+    pub port: u16,
+
+    /// Destination id (1 is the default output of the controller)
+    #[config(default = 1)]
+    pub destination_id: u8,
+}
+
+pub struct DdpStrip {
+    socket: UdpSocket,
+    destination_id: u8,
+    tint: TintConf,
+    strip_length: usize,
+    sequence: AtomicU8,
+}
+
+impl DdpStrip {
+    pub fn new(conf: &DdpStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((conf.host.as_str(), conf.port))?;
+
+        Ok(DdpStrip {
+            socket,
+            destination_id: conf.destination_id,
+            tint: tint.clone(),
+            strip_length,
+            sequence: AtomicU8::new(0),
+        })
+    }
+}
+
+impl Strip for DdpStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let colors: Vec<u8> = led_colors
+            .into_iter()
+            .flat_map(|led_color| led_color.apply_tint(&self.tint))
+            .collect();
+
+        // Sequence numbers run from 1 to 15, 0 means unused
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) % 15 + 1;
+
+        for packet in encode_packets(&colors, sequence, self.destination_id) {
+            self.socket.send(&packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the frame into DDP packets, only the last one carries the push flag so the
+/// controller shows the frame once it is complete
+pub fn encode_packets(colors: &[u8], sequence: u8, destination_id: u8) -> Vec<Vec<u8>> {
+    let chunks = colors.len().div_ceil(DDP_MAX_DATA).max(1);
+
+    (0..chunks)
+        .map(|i| {
+            let offset = i * DDP_MAX_DATA;
+            let data = &colors[offset..(offset + DDP_MAX_DATA).min(colors.len())];
+            let flags = if i + 1 == chunks {
+                DDP_VERSION | DDP_PUSH
+            } else {
+                DDP_VERSION
+            };
+
+            let mut packet = Vec::with_capacity(10 + data.len());
+            packet.extend_from_slice(&[flags, sequence & 0x0f, DDP_TYPE_RGB8, destination_id]);
+            packet.extend_from_slice(&(offset as u32).to_be_bytes());
+            packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            packet.extend_from_slice(data);
+            packet
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::core::led_color::LedColor;
+
+    /// Header fields of a received packet together with its data
+    struct Packet {
+        flags: u8,
+        sequence: u8,
+        data_type: u8,
+        destination_id: u8,
+        offset: usize,
+        data: Vec<u8>,
+    }
+
+    fn colors(len: usize) -> Vec<[u8; 3]> {
+        (0..len)
+            .map(|i| [i as u8, (i >> 8) as u8, 255 - i as u8])
+            .collect()
+    }
+
+    /// Strip sending to a socket bound on localhost, returned with that socket
+    fn strip(destination_id: u8, strip_length: usize) -> (DdpStrip, UdpSocket) {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let conf = DdpStripConf {
+            host: "127.0.0.1".into(),
+            port: receiver.local_addr().unwrap().port(),
+            destination_id,
+        };
+        let strip = DdpStrip::new(&conf, &TintConf::identity(), strip_length).unwrap();
+
+        (strip, receiver)
+    }
+
+    fn send(strip: &DdpStrip, colors: &[[u8; 3]]) {
+        let led_sequence: LedSequence = colors.iter().map(|&rgb| LedColor::from(rgb)).collect();
+        strip.set_leds(&led_sequence).unwrap();
+    }
+
+    fn receive(receiver: &UdpSocket) -> Packet {
+        let mut buf = [0; 2048];
+        let len = receiver.recv(&mut buf).unwrap();
+        let packet = &buf[..len];
+
+        let length = u16::from_be_bytes([packet[8], packet[9]]) as usize;
+        assert_eq!(len, 10 + length);
+
+        Packet {
+            flags: packet[0],
+            sequence: packet[1],
+            data_type: packet[2],
+            destination_id: packet[3],
+            offset: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]) as usize,
+            data: packet[10..].to_vec(),
+        }
+    }
+
+    fn assert_nothing_left(receiver: &UdpSocket) {
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut [0; 16]).is_err());
+    }
+
+    #[test]
+    fn short_frame_fits_one_pushed_packet() {
+        let (strip, receiver) = strip(1, 10);
+        let sent = colors(10);
+        send(&strip, &sent);
+
+        let packet = receive(&receiver);
+        assert_eq!(packet.flags, DDP_VERSION | DDP_PUSH);
+        assert_eq!(packet.sequence, 1);
+        assert_eq!(packet.data_type, DDP_TYPE_RGB8);
+        assert_eq!(packet.destination_id, 1);
+        assert_eq!(packet.offset, 0);
+        assert_eq!(packet.data, sent.concat());
+
+        assert_nothing_left(&receiver);
+    }
+
+    #[test]
+    fn long_frame_is_split_and_pushed_once() {
+        let (strip, receiver) = strip(3, 1000);
+        let sent = colors(1000);
+        send(&strip, &sent);
+
+        let packets: Vec<Packet> = (0..3).map(|_| receive(&receiver)).collect();
+        assert_nothing_left(&receiver);
+
+        let layout: Vec<(usize, usize)> = packets
+            .iter()
+            .map(|packet| (packet.offset, packet.data.len()))
+            .collect();
+        assert_eq!(layout, [(0, 1440), (1440, 1440), (2880, 120)]);
+
+        let flags: Vec<u8> = packets.iter().map(|packet| packet.flags).collect();
+        assert_eq!(flags, [DDP_VERSION, DDP_VERSION, DDP_VERSION | DDP_PUSH]);
+
+        assert!(packets
+            .iter()
+            .all(|packet| packet.destination_id == 3 && packet.sequence == 1));
+
+        let mut frame = vec![0; 3000];
+        for packet in &packets {
+            frame[packet.offset..packet.offset + packet.data.len()].copy_from_slice(&packet.data);
+        }
+        assert_eq!(frame, sent.concat());
+    }
+
+    #[test]
+    fn exact_multiple_has_no_empty_packet() {
+        let packets = encode_packets(&[7; DDP_MAX_DATA * 2], 1, 1);
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1][0], DDP_VERSION | DDP_PUSH);
+        assert_eq!(packets[1].len(), 10 + DDP_MAX_DATA);
+    }
+
+    #[test]
+    fn sequence_wraps_from_15_to_1() {
+        let (strip, receiver) = strip(1, 1);
+
+        let sequences: Vec<u8> = (0..16)
+            .map(|_| {
+                send(&strip, &colors(1));
+                receive(&receiver).sequence
+            })
+            .collect();
+
+        assert_eq!(sequences, [(1..=15).collect::<Vec<u8>>(), vec![1]].concat());
+    }
+}
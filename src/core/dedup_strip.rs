@@ -0,0 +1,43 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+
+/// Drops frames identical to the last one sent. The last frame is still resent every
+/// `keepalive`, since network receivers like WLED fall back to their own effects when
+/// nothing arrives for a while.
+pub struct DedupStrip {
+    strip: Box<dyn Strip>,
+    keepalive: Duration,
+    last: Mutex<Option<(LedSequence, Instant)>>,
+}
+
+impl DedupStrip {
+    pub fn new(strip: Box<dyn Strip>, keepalive: Duration) -> Self {
+        DedupStrip {
+            strip,
+            keepalive,
+            last: Mutex::new(None),
+        }
+    }
+}
+
+impl Strip for DedupStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        let mut last = self.last.lock().unwrap();
+
+        if let Some((last_colors, sent_at)) = &*last {
+            if last_colors == led_colors && sent_at.elapsed() < self.keepalive {
+                return Ok(());
+            }
+        }
+
+        self.strip.set_leds(led_colors)?;
+        *last = Some((led_colors.clone(), Instant::now()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::TintConf;
+use crate::core::led_sequence::LedSequence;
+use crate::errors::PLightError::WrongLength;
+
+/// Channels carried by one DMX universe
+pub const UNIVERSE_CHANNELS: usize = 512;
+/// Whole RGB pixels that fit into one DMX universe
+pub const UNIVERSE_PIXELS: usize = UNIVERSE_CHANNELS / 3;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct DmxStripConf {
+    /// Receiver address. sACN falls back to multicast and Art-Net to broadcast when empty
+    pub host: Option<String>,
+
+    /// Universe of the first LED
+    #[config(default = 1)]
+    pub universe: u16,
+
+    /// Channel of the first LED inside the first universe (starting from 0)
+    #[config(default = 0)]
+    pub channel_offset: usize,
+
+    /// sACN priority (0-200)
+    #[config(default = 100)]
+    pub priority: u8,
+
+    /// sACN source name shown by receivers
+    #[config(default = "PLight")]
+    pub source_name: String,
+}
+
+/// Splits tinted LED colors into per-universe DMX data, returning pairs of universe and
+/// channel values. Pixels never straddle two universes, so every universe carries at most
+/// [`UNIVERSE_PIXELS`] of them.
+pub fn split_into_universes(
+    led_colors: &LedSequence,
+    tint: &TintConf,
+    strip_length: usize,
+    first_universe: u16,
+    channel_offset: usize,
+) -> Result<Vec<(u16, Vec<u8>)>> {
+    if led_colors.len() != strip_length {
+        return Err(WrongLength {
+            given: led_colors.len(),
+            actual: strip_length,
+        }
+        .into());
+    }
+
+    let mut universes: Vec<(u16, Vec<u8>)> = Vec::new();
+
+    // The offset only applies to the first universe, the following ones start at channel 0
+    let first_pixels = UNIVERSE_CHANNELS.saturating_sub(channel_offset) / 3;
+
+    for (i, led_color) in led_colors.into_iter().enumerate() {
+        let (universe, channel) = match i.checked_sub(first_pixels) {
+            None => (first_universe, channel_offset + i * 3),
+            Some(i) => (
+                first_universe + (1 + i / UNIVERSE_PIXELS) as u16,
+                i % UNIVERSE_PIXELS * 3,
+            ),
+        };
+
+        if universes.last().is_none_or(|(u, _)| *u != universe) {
+            universes.push((universe, vec![0; channel]));
+        }
+
+        if let Some((_, data)) = universes.last_mut() {
+            data.resize(channel, 0);
+            data.extend_from_slice(&led_color.apply_tint(tint));
+        }
+    }
+
+    Ok(universes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::led_color::LedColor;
+
+    fn sequence(len: usize) -> LedSequence {
+        (0..len)
+            .map(|i| LedColor::from([i as u8, (i >> 8) as u8, 7]))
+            .collect()
+    }
+
+    fn split(len: usize, channel_offset: usize) -> Vec<(u16, Vec<u8>)> {
+        split_into_universes(
+            &sequence(len),
+            &TintConf::identity(),
+            len,
+            1,
+            channel_offset,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn full_universes_carry_whole_pixels() {
+        let universes = split(UNIVERSE_PIXELS * 2 + 1, 0);
+
+        let layout: Vec<(u16, usize)> = universes
+            .iter()
+            .map(|(universe, data)| (*universe, data.len()))
+            .collect();
+        assert_eq!(layout, [(1, 510), (2, 510), (3, 3)]);
+
+        assert_eq!(universes[1].1[..3], [170, 0, 7]);
+        assert_eq!(universes[2].1, [84, 1, 7]);
+    }
+
+    #[test]
+    fn channel_offset_only_shifts_first_universe() {
+        let universes = split(UNIVERSE_PIXELS * 2, 1);
+
+        let (universe, data) = &universes[0];
+        assert_eq!(*universe, 1);
+        assert_eq!(data.len(), 1 + UNIVERSE_PIXELS * 3);
+        assert_eq!(data[..4], [0, 0, 0, 7]);
+
+        let (universe, data) = &universes[1];
+        assert_eq!(*universe, 2);
+        assert_eq!(data[..3], [170, 0, 7]);
+        assert_eq!(data.len(), UNIVERSE_PIXELS * 3);
+    }
+
+    #[test]
+    fn large_offset_leaves_room_for_fewer_pixels() {
+        let universes = split(5, 506);
+
+        assert_eq!(universes[0].0, 1);
+        assert_eq!(universes[0].1.len(), 512);
+        assert_eq!(universes[0].1[506..], [0, 0, 7, 1, 0, 7]);
+
+        assert_eq!(universes[1].0, 2);
+        assert_eq!(universes[1].1[..3], [2, 0, 7]);
+        assert_eq!(universes[1].1.len(), 9);
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        assert!(split_into_universes(&sequence(3), &TintConf::identity(), 4, 1, 0).is_err());
+    }
+}
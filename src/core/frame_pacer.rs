@@ -0,0 +1,37 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Keeps the poll loop at a target frame rate by sleeping until the next frame deadline
+pub struct FramePacer {
+    frame_time: Option<Duration>,
+    deadline: Instant,
+}
+
+impl FramePacer {
+    /// `fps` of zero or below disables pacing
+    pub fn new(fps: f64) -> Self {
+        let frame_time = (fps > 0.0).then(|| Duration::from_secs_f64(1.0 / fps));
+
+        FramePacer {
+            frame_time,
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Sleeps until the end of the current frame. Deadlines advance by whole frames, so a
+    /// slow frame is not followed by a burst of fast ones trying to catch up.
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+
+        self.deadline += frame_time;
+        let now = Instant::now();
+
+        if self.deadline > now {
+            sleep(self.deadline - now);
+        } else {
+            self.deadline = now;
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! On-disk format for LED sequence streams.
+//!
+//! A file starts with `"PLFR" | version | width (u16) | height (u16) | bottom_gap (u16) |
+//! led count (u32) | layout fingerprint (u64)` and is followed by frames of `timestamp in
+//! microseconds (u64) | led count * rgb`. All numbers are little endian, colors are stored
+//! before tinting. Version 1 files have no layout fingerprint.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::config::StripConf;
+use crate::core::layout::Layout;
+use crate::core::led_color::LedColor;
+use crate::core::led_sequence::LedSequence;
+use crate::errors::PLightError::{WrongLength, WrongRecording};
+
+const MAGIC: &[u8; 4] = b"PLFR";
+const VERSION: u8 = 2;
+const V1_HEADER_SIZE: u64 = 15;
+const HEADER_SIZE: u64 = 23;
+
+/// Strip layout the frames were recorded with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RecordingHeader {
+    pub width: u16,
+    pub height: u16,
+    pub bottom_gap: u16,
+    pub led_count: u32,
+    /// [`Layout::fingerprint`], `None` for recordings older than the fingerprint
+    pub layout_fingerprint: Option<u64>,
+}
+
+impl RecordingHeader {
+    pub fn new(conf: &StripConf, layout: &Layout) -> Self {
+        RecordingHeader {
+            width: conf.width as u16,
+            height: conf.height as u16,
+            bottom_gap: conf.bottom_gap as u16,
+            led_count: layout.len() as u32,
+            layout_fingerprint: Some(layout.fingerprint()),
+        }
+    }
+}
+
+pub struct FrameRecorder {
+    writer: BufWriter<File>,
+    led_count: usize,
+    started: Instant,
+}
+
+impl FrameRecorder {
+    pub fn create(path: &Path, header: RecordingHeader) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&header.width.to_le_bytes())?;
+        writer.write_all(&header.height.to_le_bytes())?;
+        writer.write_all(&header.bottom_gap.to_le_bytes())?;
+        writer.write_all(&header.led_count.to_le_bytes())?;
+        writer.write_all(&header.layout_fingerprint.unwrap_or(0).to_le_bytes())?;
+
+        Ok(FrameRecorder {
+            writer,
+            led_count: header.led_count as usize,
+            started: Instant::now(),
+        })
+    }
+
+    /// Appends a frame stamped with the time since the recording started. Every frame is
+    /// flushed, so a crash leaves a usable file behind.
+    pub fn record(&mut self, led_sequence: &LedSequence) -> Result<()> {
+        if led_sequence.len() != self.led_count {
+            return Err(WrongLength {
+                given: led_sequence.len(),
+                actual: self.led_count,
+            }
+            .into());
+        }
+
+        let timestamp = self.started.elapsed().as_micros() as u64;
+        self.writer.write_all(&timestamp.to_le_bytes())?;
+        for led_color in led_sequence {
+            self.writer.write_all(&led_color.to_rgb8())?;
+        }
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+pub struct FrameReader {
+    reader: BufReader<File>,
+    header: RecordingHeader,
+    header_size: u64,
+}
+
+impl FrameReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut raw = [0; HEADER_SIZE as usize];
+        reader.read_exact(&mut raw[..V1_HEADER_SIZE as usize])?;
+        let header_size = match (&raw[0..4] == MAGIC, raw[4]) {
+            (true, 1) => V1_HEADER_SIZE,
+            (true, VERSION) => HEADER_SIZE,
+            _ => {
+                return Err(WrongRecording {
+                    given: path.display().to_string(),
+                }
+                .into())
+            }
+        };
+        reader.read_exact(&mut raw[V1_HEADER_SIZE as usize..header_size as usize])?;
+
+        let header = RecordingHeader {
+            width: u16::from_le_bytes([raw[5], raw[6]]),
+            height: u16::from_le_bytes([raw[7], raw[8]]),
+            bottom_gap: u16::from_le_bytes([raw[9], raw[10]]),
+            led_count: u32::from_le_bytes([raw[11], raw[12], raw[13], raw[14]]),
+            layout_fingerprint: (header_size == HEADER_SIZE)
+                .then(|| u64::from_le_bytes(raw[15..23].try_into().unwrap())),
+        };
+
+        Ok(FrameReader {
+            reader,
+            header,
+            header_size,
+        })
+    }
+
+    pub fn header(&self) -> RecordingHeader {
+        self.header
+    }
+
+    /// Reads the next frame with its timestamp, `None` at the end of the recording
+    pub fn next_frame(&mut self) -> Result<Option<(Duration, LedSequence)>> {
+        let mut timestamp = [0; 8];
+        match self.reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut colors = vec![0; self.header.led_count as usize * 3];
+        match self.reader.read_exact(&mut colors) {
+            Ok(()) => {}
+            // A frame cut short by a crash ends the recording
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let led_sequence = colors
+            .chunks_exact(3)
+            .map(|rgb| LedColor::from([rgb[0], rgb[1], rgb[2]]))
+            .collect();
+
+        Ok(Some((
+            Duration::from_micros(u64::from_le_bytes(timestamp)),
+            led_sequence,
+        )))
+    }
+
+    pub fn rewind(&mut self) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(self.header_size))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use confique::Config;
+
+    use super::*;
+    use crate::core::layout::LayoutConf;
+
+    fn frame(shift: u8, len: usize) -> LedSequence {
+        (0..len)
+            .map(|i| LedColor::from([i as u8, shift, 255 - i as u8]))
+            .collect()
+    }
+
+    fn colors(led_sequence: &LedSequence) -> Vec<[u8; 3]> {
+        led_sequence
+            .into_iter()
+            .map(|led_color| led_color.to_rgb8())
+            .collect()
+    }
+
+    fn header(layout: &Layout) -> RecordingHeader {
+        let conf = StripConf::builder().load().unwrap();
+        RecordingHeader::new(&conf, layout)
+    }
+
+    #[test]
+    fn write_read_roundtrip() {
+        let path = env::temp_dir().join(format!("plight_roundtrip_{}.plfr", process::id()));
+        let layout = Layout::new(&LayoutConf::builder().load().unwrap(), 8, 5, 2).unwrap();
+        let header = header(&layout);
+        let frames = [frame(0, layout.len()), frame(9, layout.len())];
+
+        let mut recorder = FrameRecorder::create(&path, header).unwrap();
+        for led_sequence in &frames {
+            recorder.record(led_sequence).unwrap();
+        }
+        drop(recorder);
+
+        let mut reader = FrameReader::open(&path).unwrap();
+        assert_eq!(reader.header(), header);
+        assert_eq!(
+            reader.header().layout_fingerprint,
+            Some(layout.fingerprint())
+        );
+
+        let mut read = Vec::new();
+        while let Some((timestamp, led_sequence)) = reader.next_frame().unwrap() {
+            read.push((timestamp, colors(&led_sequence)));
+        }
+        assert_eq!(read.len(), 2);
+        assert!(read[0].0 <= read[1].0);
+        assert_eq!(read[0].1, colors(&frames[0]));
+        assert_eq!(read[1].1, colors(&frames[1]));
+
+        reader.rewind().unwrap();
+        let (_, first) = reader.next_frame().unwrap().unwrap();
+        assert_eq!(colors(&first), colors(&frames[0]));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn version_1_has_no_fingerprint() {
+        let path = env::temp_dir().join(format!("plight_version_1_{}.plfr", process::id()));
+        let mut file = MAGIC.to_vec();
+        file.push(1);
+        file.extend_from_slice(&[8, 0, 5, 0, 2, 0, 2, 0, 0, 0]);
+        file.extend_from_slice(&7_u64.to_le_bytes());
+        file.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        fs::write(&path, file).unwrap();
+
+        let mut reader = FrameReader::open(&path).unwrap();
+        assert_eq!(reader.header().led_count, 2);
+        assert_eq!(reader.header().layout_fingerprint, None);
+
+        let (timestamp, led_sequence) = reader.next_frame().unwrap().unwrap();
+        assert_eq!(timestamp, Duration::from_micros(7));
+        assert_eq!(colors(&led_sequence), [[1, 2, 3], [4, 5, 6]]);
+
+        reader.rewind().unwrap();
+        assert!(reader.next_frame().unwrap().is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,641 @@
+//! Versioned framed serial protocol spoken by `arduino/plight_v2`.
+//!
+//! Every packet in both directions is `"PL" | version | kind | length (u16 BE) | payload |
+//! crc16 (u16 BE)`, where the CRC covers everything between the magic and the CRC itself.
+//!
+//! Besides full frames the firmware accepts run-length encoded frames (`run | r | g | b` per
+//! run) and delta frames (`start (u16 BE) | count | count * rgb` per changed span).
+
+use std::io::{self, Read, Write};
+
+use anyhow::Result;
+
+use crate::errors::PLightError::{
+    FrameRejected, LedCountMismatch, MalformedReply, ReplyTimeout, UnsupportedProtocolVersion,
+};
+use crate::utils::checksum::crc16;
+
+pub const MAGIC: [u8; 2] = *b"PL";
+pub const VERSION: u8 = 2;
+/// Largest payload accepted from the firmware
+pub const MAX_PAYLOAD: usize = 4096;
+
+pub const KIND_HELLO: u8 = 0x01;
+pub const KIND_HELLO_REPLY: u8 = 0x02;
+pub const KIND_ACK: u8 = 0x06;
+pub const KIND_FRAME: u8 = 0x10;
+pub const KIND_RLE_FRAME: u8 = 0x11;
+pub const KIND_DELTA_FRAME: u8 = 0x12;
+pub const KIND_NACK: u8 = 0x15;
+
+pub const NACK_CRC: u8 = 1;
+pub const NACK_LENGTH: u8 = 2;
+pub const NACK_KIND: u8 = 3;
+pub const NACK_VERSION: u8 = 4;
+
+pub fn encode_packet(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(8 + payload.len());
+    packet.extend_from_slice(&MAGIC);
+    packet.extend_from_slice(&[VERSION, kind]);
+    packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    let crc = crc16(&packet[MAGIC.len()..]);
+    packet.extend_from_slice(&crc.to_be_bytes());
+    packet
+}
+
+/// Reads the next packet, skipping anything before the magic such as the greeting
+pub fn read_packet(port: &mut impl Read) -> Result<(u8, Vec<u8>)> {
+    let mut matched = 0;
+    while matched < MAGIC.len() {
+        let byte = read_byte(port)?;
+        matched = match byte {
+            b if b == MAGIC[matched] => matched + 1,
+            b if b == MAGIC[0] => 1,
+            _ => 0,
+        };
+    }
+
+    let mut header = [0; 4];
+    read_exact(port, &mut header)?;
+    let [version, kind, hi, lo] = header;
+
+    if version != VERSION {
+        return Err(UnsupportedProtocolVersion {
+            given: version,
+            expected: VERSION,
+        }
+        .into());
+    }
+
+    let length = u16::from_be_bytes([hi, lo]) as usize;
+    if length > MAX_PAYLOAD {
+        return Err(MalformedReply.into());
+    }
+
+    let mut payload = vec![0; length];
+    read_exact(port, &mut payload)?;
+
+    let mut crc = [0; 2];
+    read_exact(port, &mut crc)?;
+
+    let mut checked = header.to_vec();
+    checked.extend_from_slice(&payload);
+    if crc16(&checked) != u16::from_be_bytes(crc) {
+        return Err(MalformedReply.into());
+    }
+
+    Ok((kind, payload))
+}
+
+/// Asks the firmware for its LED count and checks it against the configured one
+pub fn negotiate(port: &mut (impl Read + Write), strip_length: usize) -> Result<()> {
+    port.write_all(&encode_packet(KIND_HELLO, &[]))?;
+    port.flush()?;
+
+    match read_packet(port)? {
+        (KIND_HELLO_REPLY, payload) if payload.len() == 2 => {
+            let num_leds = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+            if num_leds != strip_length {
+                return Err(LedCountMismatch {
+                    given: strip_length,
+                    actual: num_leds,
+                }
+                .into());
+            }
+            Ok(())
+        }
+        (KIND_NACK, payload) => Err(nack_error(&payload)),
+        _ => Err(MalformedReply.into()),
+    }
+}
+
+/// Remembers what the firmware shows to send compressed frames against it
+pub struct FrameEncoder {
+    compression: bool,
+    keyframe_interval: u32,
+    previous: Option<Vec<u8>>,
+    since_keyframe: u32,
+}
+
+impl FrameEncoder {
+    pub fn new(compression: bool, keyframe_interval: u32) -> Self {
+        FrameEncoder {
+            compression,
+            keyframe_interval,
+            previous: None,
+            since_keyframe: 0,
+        }
+    }
+
+    /// Forgets the shown frame, so the next one goes out in full
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Picks the smallest packet for the frame. A full keyframe is forced after
+    /// `keyframe_interval` compressed ones so a missed packet can not stick forever.
+    fn encode(&self, colors: &[u8]) -> (u8, Vec<u8>) {
+        let full = (KIND_FRAME, colors.to_vec());
+
+        let previous = match &self.previous {
+            Some(previous) if self.compression && self.since_keyframe < self.keyframe_interval => {
+                previous
+            }
+            _ => return full,
+        };
+
+        [
+            (KIND_RLE_FRAME, encode_rle(colors)),
+            (KIND_DELTA_FRAME, encode_delta(previous, colors)),
+        ]
+        .into_iter()
+        .filter(|(_, payload)| payload.len() < colors.len())
+        .min_by_key(|(_, payload)| payload.len())
+        .unwrap_or(full)
+    }
+
+    fn sent(&mut self, kind: u8, colors: &[u8]) {
+        self.since_keyframe = match kind {
+            KIND_FRAME => 0,
+            _ => self.since_keyframe + 1,
+        };
+        self.previous = Some(colors.to_vec());
+    }
+}
+
+/// Sends one frame of RGB bytes and waits for its acknowledgement
+pub fn send_frame(
+    port: &mut (impl Read + Write),
+    encoder: &mut FrameEncoder,
+    colors: &[u8],
+) -> Result<()> {
+    let (kind, payload) = encoder.encode(colors);
+
+    let result = send_packet(port, kind, &payload);
+    match result {
+        Ok(()) => encoder.sent(kind, colors),
+        Err(_) => encoder.reset(),
+    }
+    result
+}
+
+fn send_packet(port: &mut (impl Read + Write), kind: u8, payload: &[u8]) -> Result<()> {
+    port.write_all(&encode_packet(kind, payload))?;
+    port.flush()?;
+
+    match read_packet(port)? {
+        (KIND_ACK, _) => Ok(()),
+        (KIND_NACK, payload) => Err(nack_error(&payload)),
+        _ => Err(MalformedReply.into()),
+    }
+}
+
+/// Runs of identical pixels covering the whole frame
+pub fn encode_rle(colors: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+
+    for pixel in colors.chunks_exact(3) {
+        let len = payload.len();
+        if len >= 4 && payload[len - 4] < u8::MAX && payload[len - 3..] == *pixel {
+            payload[len - 4] += 1;
+        } else {
+            payload.push(1);
+            payload.extend_from_slice(pixel);
+        }
+    }
+
+    payload
+}
+
+/// Spans of pixels that differ from the previous frame. Unchanged gaps of a single pixel are
+/// sent along, as they cost as much as a new span header.
+pub fn encode_delta(previous: &[u8], colors: &[u8]) -> Vec<u8> {
+    let pixels = colors.len() / 3;
+    let changed = |i: usize| previous.get(i * 3..i * 3 + 3) != colors.get(i * 3..i * 3 + 3);
+
+    let mut payload = Vec::new();
+    let mut i = 0;
+
+    while i < pixels {
+        if !changed(i) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut end = i + 1;
+        while end < pixels && end - start < u8::MAX as usize {
+            if changed(end) {
+                end += 1;
+            } else if end + 1 < pixels && end + 1 - start < u8::MAX as usize && changed(end + 1) {
+                end += 2;
+            } else {
+                break;
+            }
+        }
+
+        payload.extend_from_slice(&(start as u16).to_be_bytes());
+        payload.push((end - start) as u8);
+        payload.extend_from_slice(&colors[start * 3..end * 3]);
+        i = end;
+    }
+
+    payload
+}
+
+fn nack_error(payload: &[u8]) -> anyhow::Error {
+    match payload.first() {
+        Some(&reason) => FrameRejected { reason }.into(),
+        None => MalformedReply.into(),
+    }
+}
+
+fn read_byte(port: &mut impl Read) -> Result<u8> {
+    let mut byte = [0; 1];
+    read_exact(port, &mut byte)?;
+    Ok(byte[0])
+}
+
+/// Timeouts are the firmware's fault, any other I/O error means the port is gone
+fn read_exact(port: &mut impl Read, buf: &mut [u8]) -> Result<()> {
+    match port.read_exact(buf) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(ReplyTimeout.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::errors::PLightError;
+
+    /// In-memory serial line, reads time out once the prepared replies are used up
+    #[derive(Default)]
+    struct Wire {
+        replies: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl Wire {
+        fn replying(replies: &[Vec<u8>]) -> Self {
+            Wire {
+                replies: Cursor::new(replies.concat()),
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Wire {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.replies.read(buf)? {
+                0 if !buf.is_empty() => Err(io::ErrorKind::TimedOut.into()),
+                read => Ok(read),
+            }
+        }
+    }
+
+    impl Write for Wire {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn error(result: Result<()>) -> PLightError {
+        result.unwrap_err().downcast().unwrap()
+    }
+
+    fn hello_reply(num_leds: u16) -> Vec<u8> {
+        encode_packet(KIND_HELLO_REPLY, &num_leds.to_be_bytes())
+    }
+
+    #[test]
+    fn packet_layout() {
+        let packet = encode_packet(KIND_FRAME, &[1, 2, 3]);
+
+        assert_eq!(
+            packet[..9],
+            [b'P', b'L', VERSION, KIND_FRAME, 0, 3, 1, 2, 3]
+        );
+        assert_eq!(packet[9..], crc16(&packet[2..9]).to_be_bytes());
+    }
+
+    #[test]
+    fn read_packet_skips_greeting() {
+        let mut wire = Wire::replying(&[b"Ada\nP".to_vec(), encode_packet(KIND_ACK, &[7])]);
+
+        assert_eq!(read_packet(&mut wire).unwrap(), (KIND_ACK, vec![7]));
+    }
+
+    #[test]
+    fn negotiate_accepts_matching_led_count() {
+        let mut wire = Wire::replying(&[b"Ada\n".to_vec(), hello_reply(81)]);
+
+        negotiate(&mut wire, 81).unwrap();
+        assert_eq!(wire.sent, encode_packet(KIND_HELLO, &[]));
+    }
+
+    #[test]
+    fn negotiate_rejects_other_led_count() {
+        let mut wire = Wire::replying(&[hello_reply(60)]);
+
+        assert!(matches!(
+            error(negotiate(&mut wire, 81)),
+            PLightError::LedCountMismatch {
+                given: 81,
+                actual: 60
+            }
+        ));
+    }
+
+    #[test]
+    fn negotiate_reports_nack_reason() {
+        let mut wire = Wire::replying(&[encode_packet(KIND_NACK, &[NACK_KIND])]);
+
+        assert!(matches!(
+            error(negotiate(&mut wire, 81)),
+            PLightError::FrameRejected { reason: NACK_KIND }
+        ));
+    }
+
+    #[test]
+    fn negotiate_rejects_other_version() {
+        let mut packet = hello_reply(81);
+        packet[2] = 3;
+        let crc = crc16(&packet[2..packet.len() - 2]);
+        let len = packet.len();
+        packet[len - 2..].copy_from_slice(&crc.to_be_bytes());
+        let mut wire = Wire::replying(&[packet]);
+
+        assert!(matches!(
+            error(negotiate(&mut wire, 81)),
+            PLightError::UnsupportedProtocolVersion {
+                given: 3,
+                expected: VERSION
+            }
+        ));
+    }
+
+    #[test]
+    fn corrupted_reply_is_malformed() {
+        let mut packet = hello_reply(81);
+        packet[6] ^= 0xff;
+        let mut wire = Wire::replying(&[packet]);
+
+        assert!(matches!(
+            error(negotiate(&mut wire, 81)),
+            PLightError::MalformedReply
+        ));
+    }
+
+    #[test]
+    fn missing_reply_times_out() {
+        let mut wire = Wire::default();
+
+        assert!(matches!(
+            error(negotiate(&mut wire, 81)),
+            PLightError::ReplyTimeout
+        ));
+    }
+
+    #[test]
+    fn acknowledged_frame() {
+        let mut wire = Wire::replying(&[encode_packet(KIND_ACK, &[])]);
+        let mut encoder = FrameEncoder::new(false, 60);
+
+        send_frame(&mut wire, &mut encoder, &[1, 2, 3]).unwrap();
+        assert_eq!(wire.sent, encode_packet(KIND_FRAME, &[1, 2, 3]));
+    }
+
+    #[test]
+    fn rejected_frame() {
+        let mut wire = Wire::replying(&[encode_packet(KIND_NACK, &[NACK_CRC])]);
+        let mut encoder = FrameEncoder::new(false, 60);
+
+        assert!(matches!(
+            error(send_frame(&mut wire, &mut encoder, &[1, 2, 3])),
+            PLightError::FrameRejected { reason: NACK_CRC }
+        ));
+    }
+
+    /// Applies a frame payload to the shown colors like `arduino/plight_v2` does, checking
+    /// the whole payload first. `None` is what the firmware answers with a NACK.
+    fn show(shown: &[u8], kind: u8, payload: &[u8]) -> Option<Vec<u8>> {
+        let num_leds = shown.len() / 3;
+        let mut leds = shown.to_vec();
+
+        match kind {
+            KIND_FRAME if payload.len() == shown.len() => leds.copy_from_slice(payload),
+            KIND_RLE_FRAME if payload.len().is_multiple_of(4) => {
+                let mut led = 0;
+                for run in payload.chunks_exact(4) {
+                    let count = run[0] as usize;
+                    if count == 0 || led + count > num_leds {
+                        return None;
+                    }
+                    for _ in 0..count {
+                        leds[led * 3..led * 3 + 3].copy_from_slice(&run[1..]);
+                        led += 1;
+                    }
+                }
+                if led != num_leds {
+                    return None;
+                }
+            }
+            KIND_DELTA_FRAME => {
+                let mut i = 0;
+                while i < payload.len() {
+                    let header = payload.get(i..i + 3)?;
+                    let start = u16::from_be_bytes([header[0], header[1]]) as usize;
+                    let count = header[2] as usize;
+                    i += 3;
+                    let span = payload.get(i..i + count * 3)?;
+                    if start + count > num_leds {
+                        return None;
+                    }
+                    leds[start * 3..(start + count) * 3].copy_from_slice(span);
+                    i += count * 3;
+                }
+            }
+            _ => return None,
+        }
+
+        Some(leds)
+    }
+
+    fn frame(pixels: &[[u8; 3]]) -> Vec<u8> {
+        pixels.concat()
+    }
+
+    /// Pseudo random but repeatable colors, with runs of equal pixels now and then
+    fn noise(num_leds: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        let mut colors = Vec::with_capacity(num_leds * 3);
+        while colors.len() < num_leds * 3 {
+            let pixel = [next() as u8, next() as u8, next() as u8];
+            for _ in 0..next() % 4 + 1 {
+                colors.extend_from_slice(&pixel);
+            }
+        }
+        colors.truncate(num_leds * 3);
+        colors
+    }
+
+    fn delta_spans(payload: &[u8]) -> Vec<(u16, u8)> {
+        let mut spans = Vec::new();
+        let mut i = 0;
+        while i < payload.len() {
+            let count = payload[i + 2];
+            spans.push((u16::from_be_bytes([payload[i], payload[i + 1]]), count));
+            i += 3 + count as usize * 3;
+        }
+        spans
+    }
+
+    #[test]
+    fn rle_roundtrip() {
+        for seed in 0..50 {
+            let colors = noise(81, seed);
+            let shown = show(&[0; 81 * 3], KIND_RLE_FRAME, &encode_rle(&colors));
+            assert_eq!(shown, Some(colors));
+        }
+    }
+
+    #[test]
+    fn rle_runs_stop_at_255() {
+        let colors = frame(&[[1, 2, 3]; 600]);
+        let payload = encode_rle(&colors);
+
+        assert_eq!(
+            payload,
+            [[255, 1, 2, 3], [255, 1, 2, 3], [90, 1, 2, 3]].concat()
+        );
+        assert_eq!(show(&[0; 600 * 3], KIND_RLE_FRAME, &payload), Some(colors));
+    }
+
+    #[test]
+    fn delta_roundtrip() {
+        for seed in 0..50 {
+            let previous = noise(300, seed);
+            let mut colors = previous.clone();
+            let changes = noise(300, seed + 1000);
+            for (i, change) in changes.chunks_exact(3).enumerate() {
+                if change[0] % 3 == 0 {
+                    colors[i * 3..i * 3 + 3].copy_from_slice(change);
+                }
+            }
+
+            let payload = encode_delta(&previous, &colors);
+            assert_eq!(
+                show(&previous, KIND_DELTA_FRAME, &payload),
+                Some(colors.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn delta_of_unchanged_frame_is_empty() {
+        let colors = noise(81, 1);
+        assert!(encode_delta(&colors, &colors).is_empty());
+    }
+
+    #[test]
+    fn delta_merges_single_pixel_gaps_only() {
+        let previous = frame(&[[0; 3]; 10]);
+        let mut colors = previous.clone();
+        for i in [1, 3, 7] {
+            colors[i * 3] = 9;
+        }
+
+        let payload = encode_delta(&previous, &colors);
+        assert_eq!(delta_spans(&payload), [(1, 3), (7, 1)]);
+        assert_eq!(show(&previous, KIND_DELTA_FRAME, &payload), Some(colors));
+    }
+
+    #[test]
+    fn delta_spans_stop_at_255() {
+        let previous = frame(&[[0; 3]; 600]);
+        let colors = frame(&[[5; 3]; 600]);
+
+        let payload = encode_delta(&previous, &colors);
+        assert_eq!(delta_spans(&payload), [(0, 255), (255, 255), (510, 90)]);
+        assert_eq!(show(&previous, KIND_DELTA_FRAME, &payload), Some(colors));
+    }
+
+    #[test]
+    fn encoder_without_compression_sends_full_frames() {
+        let mut encoder = FrameEncoder::new(false, 60);
+        let colors = frame(&[[7; 3]; 81]);
+
+        for _ in 0..3 {
+            let (kind, payload) = encoder.encode(&colors);
+            assert_eq!((kind, &payload), (KIND_FRAME, &colors));
+            encoder.sent(kind, &colors);
+        }
+    }
+
+    #[test]
+    fn encoder_forces_keyframes() {
+        let mut encoder = FrameEncoder::new(true, 3);
+        let mut shown = vec![0_u8; 81 * 3];
+        let mut kinds = Vec::new();
+
+        for i in 0..9 {
+            let mut colors = shown.clone();
+            colors[i * 3] = colors[i * 3].wrapping_add(1);
+
+            let (kind, payload) = encoder.encode(&colors);
+            shown = show(&shown, kind, &payload).unwrap();
+            assert_eq!(shown, colors);
+
+            encoder.sent(kind, &colors);
+            kinds.push(kind);
+        }
+
+        let (full, delta) = (KIND_FRAME, KIND_DELTA_FRAME);
+        assert_eq!(
+            kinds,
+            [full, delta, delta, delta, full, delta, delta, delta, full]
+        );
+    }
+
+    #[test]
+    fn encoder_picks_smallest_packet() {
+        let mut encoder = FrameEncoder::new(true, 60);
+        encoder.sent(KIND_FRAME, &noise(81, 3));
+
+        // A plain color is cheapest as one run per 255 pixels
+        let (kind, _) = encoder.encode(&frame(&[[4, 5, 6]; 81]));
+        assert_eq!(kind, KIND_RLE_FRAME);
+
+        // Nothing beats the full frame once every pixel changed to a different color
+        let gradient: Vec<[u8; 3]> = (0..81).map(|i| [i as u8, 200, 3]).collect();
+        let (kind, _) = encoder.encode(&frame(&gradient));
+        assert_eq!(kind, KIND_FRAME);
+    }
+
+    #[test]
+    fn reset_encoder_sends_full_frame() {
+        let mut encoder = FrameEncoder::new(true, 60);
+        let colors = frame(&[[1; 3]; 81]);
+        encoder.sent(KIND_FRAME, &colors);
+
+        encoder.reset();
+        assert_eq!(encoder.encode(&colors).0, KIND_FRAME);
+    }
+}
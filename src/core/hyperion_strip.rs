@@ -0,0 +1,227 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+use anyhow::Result;
+use confique::Config;
+use image::imageops::{resize, FilterType};
+use image::{ImageBuffer, Rgb};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::{StripConf, TintConf};
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::{Strip, StripMod};
+use crate::errors::PLightError::{ImageForwardUnsupported, WrongLength};
+use crate::modes::sources::SourceMod;
+use crate::utils::converters::bytes_to_base64;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct HyperionStripConf {
+    /// Address of the Hyperion/HyperHDR JSON server
+    #[config(default = "127.0.0.1")]
+    pub host: String,
+    #[config(default = 19444)]
+    pub port: u16,
+
+    /// Priority of PLight among Hyperion inputs (lower wins)
+    #[config(default = 150)]
+    pub priority: u8,
+    #[config(default = "PLight")]
+    pub origin: String,
+
+    /// What to forward (Leds, Image). Image sends the captured screen of the Screen source and
+    /// leaves LED mapping to Hyperion, it is not available for Multi outputs
+    #[config(default = "Leds")]
+    pub forward: HyperionForward,
+    /// Width the screen image is scaled down to before sending
+    #[config(default = 160)]
+    pub image_width: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum HyperionForward {
+    Leds,
+    Image,
+}
+
+/// Connection to the Hyperion JSON API, replies are only read to report failures
+pub struct HyperionClient {
+    stream: TcpStream,
+    priority: u8,
+    origin: String,
+    image_width: u32,
+}
+
+impl HyperionClient {
+    pub fn new(conf: &HyperionStripConf) -> Result<Self> {
+        let stream = TcpStream::connect((conf.host.as_str(), conf.port))?;
+        stream.set_nodelay(true)?;
+
+        let replies = BufReader::new(stream.try_clone()?);
+        thread::spawn(move || {
+            for line in replies.lines().map_while(Result::ok) {
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(reply) if reply["success"] == false => {
+                        warn!("Hyperion rejected a command: {}", reply["error"])
+                    }
+                    _ => debug!("Hyperion reply: {}", line),
+                }
+            }
+        });
+
+        Ok(HyperionClient {
+            stream,
+            priority: conf.priority,
+            origin: conf.origin.clone(),
+            image_width: conf.image_width,
+        })
+    }
+
+    /// Sets every Hyperion LED at once, the list repeats if Hyperion has more LEDs
+    pub fn send_colors(&self, colors: &[u8]) -> Result<()> {
+        self.send(&json!({
+            "command": "color",
+            "color": colors,
+            "priority": self.priority,
+            "origin": self.origin,
+        }))
+    }
+
+    pub fn send_image(&self, image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Result<()> {
+        let width = self.image_width.clamp(1, image.width().max(1));
+        let height = (image.height() as u64 * width as u64 / image.width().max(1) as u64).max(1);
+        let image = resize(image, width, height as u32, FilterType::Triangle);
+
+        self.send(&json!({
+            "command": "image",
+            "imagedata": bytes_to_base64(image.as_raw()),
+            "imagewidth": image.width(),
+            "imageheight": image.height(),
+            "format": "auto",
+            "priority": self.priority,
+            "origin": self.origin,
+        }))
+    }
+
+    fn send(&self, command: &Value) -> Result<()> {
+        let mut line = serde_json::to_vec(command)?;
+        line.push(b'\n');
+
+        let mut stream = &self.stream;
+        stream.write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// Only the Screen source has an image to forward, and it only does so for the main strip
+pub fn check_forward(conf: &StripConf, source_mode: SourceMod) -> Result<()> {
+    if conf.hyperion.forward != HyperionForward::Image {
+        return Ok(());
+    }
+
+    let in_outputs = conf
+        .outputs
+        .iter()
+        .any(|output| output.mode == StripMod::Hyperion);
+    let supported = match conf.mode {
+        StripMod::Hyperion => source_mode == SourceMod::Screen,
+        StripMod::Multi => !in_outputs,
+        _ => true,
+    };
+
+    if supported {
+        Ok(())
+    } else {
+        Err(ImageForwardUnsupported.into())
+    }
+}
+
+pub struct HyperionStrip {
+    /// Unset in image mode, where the Screen source keeps its own connection
+    client: Option<HyperionClient>,
+    tint: TintConf,
+    strip_length: usize,
+}
+
+impl HyperionStrip {
+    pub fn new(conf: &HyperionStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let client = match conf.forward {
+            HyperionForward::Leds => Some(HyperionClient::new(conf)?),
+            HyperionForward::Image => None,
+        };
+
+        Ok(HyperionStrip {
+            client,
+            tint: tint.clone(),
+            strip_length,
+        })
+    }
+}
+
+impl Strip for HyperionStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let Some(client) = &self.client else {
+            return Ok(());
+        };
+
+        let colors: Vec<u8> = led_colors
+            .into_iter()
+            .flat_map(|led_color| led_color.apply_tint(&self.tint))
+            .collect();
+        client.send_colors(&colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::core::led_color::LedColor;
+
+    #[test]
+    fn color_command_is_valid_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let conf = HyperionStripConf {
+            host: "127.0.0.1".into(),
+            port: listener.local_addr().unwrap().port(),
+            priority: 120,
+            origin: "Desk \"left\"\n\u{1}".into(),
+            forward: HyperionForward::Leds,
+            image_width: 160,
+        };
+
+        let strip = HyperionStrip::new(&conf, &TintConf::identity(), 2).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let led_sequence: LedSequence = [[1, 2, 3], [250, 0, 9]]
+            .into_iter()
+            .map(LedColor::from)
+            .collect();
+        strip.set_leds(&led_sequence).unwrap();
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        let command: Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(
+            command,
+            json!({
+                "command": "color",
+                "color": [1, 2, 3, 250, 0, 9],
+                "priority": 120,
+                "origin": "Desk \"left\"\n\u{1}",
+            })
+        );
+    }
+}
@@ -0,0 +1,703 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use confique::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::PLightError::SkippedLedOutOfRange;
+
+/// Grid `grid_positions` spreads regions from a layout file over
+const REGION_GRID: (usize, usize) = (32, 18);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+/// Direction of the strip as seen when looking at the screen
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Cells along a side without LEDs, counted left to right or top to bottom
+#[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Gap {
+    pub side: Side,
+    pub start: usize,
+    pub length: usize,
+}
+
+/// Area of the screen sampled for an LED in Hyperion's notation, 0.0 - 1.0 from the left
+/// (`h`) and top (`v`) edges. An empty area keeps the LED dark.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Region {
+    pub hmin: f64,
+    pub hmax: f64,
+    pub vmin: f64,
+    pub vmax: f64,
+}
+
+impl Region {
+    fn is_empty(&self) -> bool {
+        self.hmax <= self.hmin || self.vmax <= self.vmin
+    }
+}
+
+/// Either a bare list of regions or Hyperion's `{ "leds": [...] }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LayoutFile {
+    List(Vec<Region>),
+    Table { leds: Vec<Region> },
+}
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct LayoutConf {
+    /// JSON or TOML file with the sample region of every LED in strip order. Replaces the
+    /// rest of the layout and the strip geometry when set
+    pub file: Option<PathBuf>,
+
+    /// Corner the walk along the screen starts from (TopLeft, TopRight, BottomRight, BottomLeft)
+    #[config(default = "BottomLeft")]
+    pub start_corner: Corner,
+
+    /// Direction the strip runs in (Clockwise, CounterClockwise)
+    #[config(default = "CounterClockwise")]
+    pub direction: Direction,
+
+    /// LED cells per side, `width` and `height` by default
+    pub top: Option<usize>,
+    pub right: Option<usize>,
+    pub bottom: Option<usize>,
+    pub left: Option<usize>,
+
+    /// Gaps in addition to the centered `bottom_gap`, e.g.
+    /// `[{ side = "Top", start = 10, length = 4 }]`
+    #[config(default = [])]
+    pub gaps: Vec<Gap>,
+
+    /// LEDs between the start corner and the first LED of the strip. Unset with the default
+    /// corner and direction, the original PLight order is kept: the right half of the bottom
+    /// from right to left, then counterclockwise up to the left half of the bottom. Unset
+    /// otherwise, the strip starts right after the centered bottom gap
+    pub offset: Option<usize>,
+
+    /// Indexes of LEDs that are not on the screen edge and stay dark, e.g. the ones bent
+    /// around a corner. They do not take a cell.
+    #[config(default = [])]
+    pub skipped: Vec<usize>,
+}
+
+/// One cell on a side of the screen, `index` counted left to right or top to bottom
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cell {
+    pub side: Side,
+    pub index: usize,
+}
+
+/// Pixel rectangle of an image, end exclusive
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: (usize, usize),
+    pub y: (usize, usize),
+}
+
+impl Rect {
+    /// The whole `width x height` image
+    pub fn full(width: usize, height: usize) -> Self {
+        Rect {
+            x: (0, width),
+            y: (0, height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x.1 - self.x.0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y.1 - self.y.0
+    }
+
+    fn offset(self, x: usize, y: usize) -> Self {
+        Rect {
+            x: (self.x.0 + x, self.x.1 + x),
+            y: (self.y.0 + y, self.y.1 + y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Placement {
+    Edge(Cell),
+    Region(Region),
+}
+
+/// Placement of every LED of the strip around the screen
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layout {
+    /// Placement of every LED in strip order, `None` for dark LEDs
+    leds: Vec<Option<Placement>>,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    left: usize,
+}
+
+impl Layout {
+    pub fn new(conf: &LayoutConf, width: usize, height: usize, bottom_gap: usize) -> Result<Self> {
+        match &conf.file {
+            Some(path) => Self::from_file(path),
+            None => Self::from_edges(conf, width, height, bottom_gap),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Can not read layout file {}", path.display()))?;
+        let file: LayoutFile = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str(&content).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&content).map_err(anyhow::Error::from)
+        }
+        .with_context(|| format!("Can not parse layout file {}", path.display()))?;
+
+        let (LayoutFile::List(regions) | LayoutFile::Table { leds: regions }) = file;
+        let leds = regions
+            .into_iter()
+            .map(|region| (!region.is_empty()).then_some(Placement::Region(region)))
+            .collect();
+
+        let (columns, rows) = REGION_GRID;
+        Ok(Layout {
+            leds,
+            top: columns,
+            right: rows,
+            bottom: columns,
+            left: rows,
+        })
+    }
+
+    fn from_edges(
+        conf: &LayoutConf,
+        width: usize,
+        height: usize,
+        bottom_gap: usize,
+    ) -> Result<Self> {
+        let top = conf.top.unwrap_or(width);
+        let right = conf.right.unwrap_or(height);
+        let bottom = conf.bottom.unwrap_or(width);
+        let left = conf.left.unwrap_or(height);
+
+        let bottom_gap = bottom_gap.min(bottom);
+        let counts = |side| match side {
+            Side::Top => top,
+            Side::Right => right,
+            Side::Bottom => bottom,
+            Side::Left => left,
+        };
+
+        let original = conf.offset.is_none()
+            && conf.start_corner == Corner::BottomLeft
+            && conf.direction == Direction::CounterClockwise;
+
+        let cells: Vec<Cell> = if original {
+            original_walk(counts, bottom_gap)
+                .into_iter()
+                .filter(|cell| !in_gaps(&conf.gaps, cell))
+                .collect()
+        } else {
+            let gap_start = (bottom - bottom_gap) / 2;
+            let mut gaps = conf.gaps.clone();
+            if bottom_gap > 0 {
+                gaps.push(Gap {
+                    side: Side::Bottom,
+                    start: gap_start,
+                    length: bottom_gap,
+                });
+            }
+
+            let mut cells: Vec<Cell> = walk(conf.start_corner, conf.direction, counts)
+                .into_iter()
+                .filter(|cell| !in_gaps(&gaps, cell))
+                .collect();
+
+            let offset = conf.offset.unwrap_or_else(|| {
+                // Right after the centered bottom gap, the bottom runs right to left clockwise
+                let first = match conf.direction {
+                    Direction::CounterClockwise => Some(gap_start + bottom_gap),
+                    Direction::Clockwise => gap_start.checked_sub(1),
+                };
+                first
+                    .and_then(|index| {
+                        let first = Cell {
+                            side: Side::Bottom,
+                            index,
+                        };
+                        cells.iter().position(|cell| *cell == first)
+                    })
+                    .unwrap_or(0)
+            });
+            if !cells.is_empty() {
+                let offset = offset % cells.len();
+                cells.rotate_left(offset);
+            }
+            cells
+        };
+
+        let mut skipped = conf.skipped.clone();
+        skipped.sort_unstable();
+        skipped.dedup();
+
+        let len = cells.len() + skipped.len();
+        if let Some(&given) = skipped.last().filter(|&&i| i >= len) {
+            return Err(SkippedLedOutOfRange { given, actual: len }.into());
+        }
+        let mut cells = cells.into_iter();
+        let leds = (0..len)
+            .map(|i| {
+                if skipped.binary_search(&i).is_ok() {
+                    None
+                } else {
+                    cells.next().map(Placement::Edge)
+                }
+            })
+            .collect();
+
+        Ok(Layout {
+            leds,
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.leds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leds.is_empty()
+    }
+
+    /// Layout of the LEDs `start..start + length` alone, in reverse order when `reversed`.
+    /// The screen sides stay the same, so the LEDs keep their places.
+    pub fn segment(&self, start: usize, length: usize, reversed: bool) -> Layout {
+        let end = (start + length).min(self.leds.len());
+        let mut leds = self.leds[start.min(end)..end].to_vec();
+        if reversed {
+            leds.reverse();
+        }
+
+        Layout {
+            leds,
+            ..self.clone()
+        }
+    }
+
+    /// Rectangle sampled for every LED within `area` of the image. Sides are split evenly
+    /// between the corners, `thickness` pixels deep, regions are scaled to the area.
+    pub fn sample_rects(
+        &self,
+        area: Rect,
+        corner_p: usize,
+        thickness_p: usize,
+    ) -> Vec<Option<Rect>> {
+        let (width_p, height_p) = (area.width(), area.height());
+        let corner_x = corner_p.min(width_p / 2);
+        let corner_y = corner_p.min(height_p / 2);
+        let depth_x = thickness_p.clamp(1, width_p.max(1));
+        let depth_y = thickness_p.clamp(1, height_p.max(1));
+
+        // Splits `[corner, length - corner)` into `count` parts, at least one pixel each
+        let span = |index: usize, count: usize, length: usize, corner: usize| {
+            let inner = length - 2 * corner;
+            let start = corner + index * inner / count;
+            let end = corner + (index + 1) * inner / count;
+            (
+                start.min(length.saturating_sub(1)),
+                end.max(start + 1).min(length),
+            )
+        };
+
+        // Scales `[min, max]` of a region to pixels, at least one pixel wide
+        let scale = |min: f64, max: f64, length: usize| {
+            let start =
+                ((min.clamp(0.0, 1.0) * length as f64) as usize).min(length.saturating_sub(1));
+            let end = ((max.clamp(0.0, 1.0) * length as f64).ceil() as usize)
+                .clamp(start + 1, length.max(1));
+            (start, end)
+        };
+
+        self.leds
+            .iter()
+            .map(|placement| {
+                placement.map(|placement| match placement {
+                    Placement::Region(region) => Rect {
+                        x: scale(region.hmin, region.hmax, width_p),
+                        y: scale(region.vmin, region.vmax, height_p),
+                    },
+                    Placement::Edge(cell) => match cell.side {
+                        Side::Top => Rect {
+                            x: span(cell.index, self.top, width_p, corner_x),
+                            y: (0, depth_y),
+                        },
+                        Side::Bottom => Rect {
+                            x: span(cell.index, self.bottom, width_p, corner_x),
+                            y: (height_p - depth_y, height_p),
+                        },
+                        Side::Left => Rect {
+                            x: (0, depth_x),
+                            y: span(cell.index, self.left, height_p, corner_y),
+                        },
+                        Side::Right => Rect {
+                            x: (width_p - depth_x, width_p),
+                            y: span(cell.index, self.right, height_p, corner_y),
+                        },
+                    },
+                })
+            })
+            .map(|rect| rect.map(|rect| rect.offset(area.x.0, area.y.0)))
+            .collect()
+    }
+
+    /// Hash of the side counts and the placement of every LED. It does not depend on the
+    /// Rust version, so frame recordings store it to detect a replay on another layout.
+    pub fn fingerprint(&self) -> u64 {
+        // 64 bit FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        for count in [self.top, self.right, self.bottom, self.left] {
+            feed(&(count as u64).to_le_bytes());
+        }
+        for placement in &self.leds {
+            match placement {
+                None => feed(&[0]),
+                Some(Placement::Edge(cell)) => {
+                    feed(&[1, cell.side as u8]);
+                    feed(&(cell.index as u64).to_le_bytes());
+                }
+                Some(Placement::Region(region)) => {
+                    feed(&[2]);
+                    for value in [region.hmin, region.hmax, region.vmin, region.vmax] {
+                        feed(&value.to_bits().to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        hash
+    }
+
+    /// Columns and rows of a grid with one cell per LED along the edges and the corners empty
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.top.max(self.bottom) + 2, self.left.max(self.right) + 2)
+    }
+
+    /// Column and row of every LED in the grid from `grid_size`. Regions are placed by
+    /// their center.
+    pub fn grid_positions(&self) -> Vec<Option<(usize, usize)>> {
+        let (columns, rows) = self.grid_size();
+        let (inner_columns, inner_rows) = (columns - 2, rows - 2);
+
+        self.leds
+            .iter()
+            .map(|placement| {
+                placement.map(|placement| match placement {
+                    Placement::Region(region) => {
+                        let x = ((region.hmin + region.hmax) / 2.0).clamp(0.0, 1.0);
+                        let y = ((region.vmin + region.vmax) / 2.0).clamp(0.0, 1.0);
+                        (
+                            (x * (columns - 1) as f64).round() as usize,
+                            (y * (rows - 1) as f64).round() as usize,
+                        )
+                    }
+                    Placement::Edge(cell) => match cell.side {
+                        Side::Top => (1 + cell.index * inner_columns / self.top, 0),
+                        Side::Bottom => (1 + cell.index * inner_columns / self.bottom, rows - 1),
+                        Side::Left => (0, 1 + cell.index * inner_rows / self.left),
+                        Side::Right => (columns - 1, 1 + cell.index * inner_rows / self.right),
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+fn in_gaps(gaps: &[Gap], cell: &Cell) -> bool {
+    gaps.iter().any(|gap| {
+        gap.side == cell.side && (gap.start..gap.start + gap.length).contains(&cell.index)
+    })
+}
+
+/// Order PLight sampled the screen in before layouts were configurable. The bottom halves
+/// are `(bottom - bottom_gap) / 2` cells each and both start at the left, so the gap ends
+/// up at the right end of the bottom side.
+fn original_walk(counts: impl Fn(Side) -> usize, bottom_gap: usize) -> Vec<Cell> {
+    let half = (counts(Side::Bottom) - bottom_gap) / 2;
+    let cell = |side| move |index| Cell { side, index };
+
+    (half..2 * half)
+        .rev()
+        .map(cell(Side::Bottom))
+        .chain((0..counts(Side::Right)).rev().map(cell(Side::Right)))
+        .chain((0..counts(Side::Top)).rev().map(cell(Side::Top)))
+        .chain((0..counts(Side::Left)).map(cell(Side::Left)))
+        .chain((0..half).map(cell(Side::Bottom)))
+        .collect()
+}
+
+/// Every cell around the screen in walk order
+fn walk(start: Corner, direction: Direction, counts: impl Fn(Side) -> usize) -> Vec<Cell> {
+    const CLOCKWISE: [Side; 4] = [Side::Top, Side::Right, Side::Bottom, Side::Left];
+
+    // Side a clockwise walk leaves the corner along
+    let first = match start {
+        Corner::TopLeft => 0,
+        Corner::TopRight => 1,
+        Corner::BottomRight => 2,
+        Corner::BottomLeft => 3,
+    };
+
+    let mut cells = Vec::new();
+    for step in 0..4 {
+        let side = match direction {
+            Direction::Clockwise => CLOCKWISE[(first + step) % 4],
+            Direction::CounterClockwise => CLOCKWISE[(first + 3 * (step + 1)) % 4],
+        };
+
+        // Clockwise runs left to right on top and top to bottom on the right
+        let ascending =
+            matches!(side, Side::Top | Side::Right) == (direction == Direction::Clockwise);
+        let count = counts(side);
+        let indexes: Box<dyn Iterator<Item = usize>> = if ascending {
+            Box::new(0..count)
+        } else {
+            Box::new((0..count).rev())
+        };
+
+        cells.extend(indexes.map(|index| Cell { side, index }));
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::PLightError;
+
+    fn default_conf() -> LayoutConf {
+        LayoutConf::builder().load().unwrap()
+    }
+
+    /// Side and index of every LED, `None` for dark ones
+    fn cells(layout: &Layout) -> Vec<Option<(Side, usize)>> {
+        layout
+            .leds
+            .iter()
+            .map(|placement| match placement {
+                Some(Placement::Edge(cell)) => Some((cell.side, cell.index)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn run(side: Side, indexes: &[usize]) -> Vec<Option<(Side, usize)>> {
+        indexes.iter().map(|&index| Some((side, index))).collect()
+    }
+
+    #[test]
+    fn original_walk_keeps_old_order() {
+        let layout = Layout::new(&default_conf(), 6, 3, 2).unwrap();
+
+        // Bottom right, right, top, left and bottom left, as the image was sampled before
+        let expected = [
+            run(Side::Bottom, &[3, 2]),
+            run(Side::Right, &[2, 1, 0]),
+            run(Side::Top, &[5, 4, 3, 2, 1, 0]),
+            run(Side::Left, &[0, 1, 2]),
+            run(Side::Bottom, &[0, 1]),
+        ]
+        .concat();
+        assert_eq!(cells(&layout), expected);
+    }
+
+    #[test]
+    fn gaps_drop_cells() {
+        let mut conf = default_conf();
+        conf.gaps = vec![
+            Gap {
+                side: Side::Top,
+                start: 1,
+                length: 2,
+            },
+            Gap {
+                side: Side::Right,
+                start: 0,
+                length: 1,
+            },
+        ];
+        let layout = Layout::new(&conf, 6, 3, 2).unwrap();
+
+        let expected = [
+            run(Side::Bottom, &[3, 2]),
+            run(Side::Right, &[2, 1]),
+            run(Side::Top, &[5, 4, 3, 0]),
+            run(Side::Left, &[0, 1, 2]),
+            run(Side::Bottom, &[0, 1]),
+        ]
+        .concat();
+        assert_eq!(cells(&layout), expected);
+    }
+
+    #[test]
+    fn walk_follows_corner_and_direction() {
+        let mut conf = default_conf();
+        conf.start_corner = Corner::TopLeft;
+        conf.direction = Direction::Clockwise;
+        conf.offset = Some(0);
+        let clockwise = [
+            run(Side::Top, &[0, 1, 2]),
+            run(Side::Right, &[0, 1]),
+            run(Side::Bottom, &[2, 1, 0]),
+            run(Side::Left, &[1, 0]),
+        ]
+        .concat();
+        assert_eq!(cells(&Layout::new(&conf, 3, 2, 0).unwrap()), clockwise);
+
+        conf.direction = Direction::CounterClockwise;
+        let counterclockwise = [
+            run(Side::Left, &[0, 1]),
+            run(Side::Bottom, &[0, 1, 2]),
+            run(Side::Right, &[1, 0]),
+            run(Side::Top, &[2, 1, 0]),
+        ]
+        .concat();
+        assert_eq!(
+            cells(&Layout::new(&conf, 3, 2, 0).unwrap()),
+            counterclockwise
+        );
+
+        conf.direction = Direction::Clockwise;
+        conf.offset = Some(2);
+        let mut shifted = clockwise.clone();
+        shifted.rotate_left(2);
+        assert_eq!(cells(&Layout::new(&conf, 3, 2, 0).unwrap()), shifted);
+    }
+
+    #[test]
+    fn walk_starts_after_bottom_gap() {
+        let mut conf = default_conf();
+        conf.start_corner = Corner::BottomRight;
+
+        // Counterclockwise the bottom runs left to right, so the gap is left behind first
+        let layout = Layout::new(&conf, 6, 2, 2).unwrap();
+        assert_eq!(cells(&layout)[..2], run(Side::Bottom, &[4, 5]));
+        assert_eq!(
+            cells(&layout)[layout.len() - 2..],
+            run(Side::Bottom, &[0, 1])
+        );
+
+        conf.direction = Direction::Clockwise;
+        let layout = Layout::new(&conf, 6, 2, 2).unwrap();
+        assert_eq!(cells(&layout)[..2], run(Side::Bottom, &[1, 0]));
+        assert_eq!(
+            cells(&layout)[layout.len() - 2..],
+            run(Side::Bottom, &[5, 4])
+        );
+    }
+
+    #[test]
+    fn skipped_leds_stay_dark_without_a_cell() {
+        let mut conf = default_conf();
+        conf.skipped = vec![5, 0, 5, 16];
+        let layout = Layout::new(&conf, 6, 3, 2).unwrap();
+
+        let mut expected = cells(&Layout::new(&default_conf(), 6, 3, 2).unwrap());
+        for i in [0, 5, 16] {
+            expected.insert(i, None);
+        }
+        assert_eq!(layout.len(), 19);
+        assert_eq!(cells(&layout), expected);
+    }
+
+    #[test]
+    fn skipped_led_out_of_range() {
+        let mut conf = default_conf();
+        conf.skipped = vec![3, 500];
+
+        let err = Layout::new(&conf, 6, 3, 2).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PLightError>(),
+            Some(PLightError::SkippedLedOutOfRange {
+                given: 500,
+                actual: 18
+            })
+        ));
+
+        // One past the last cell is the last LED once another one is skipped
+        conf.skipped = vec![3, 17];
+        assert!(Layout::new(&conf, 6, 3, 2).is_ok());
+        conf.skipped = vec![3, 18];
+        assert!(Layout::new(&conf, 6, 3, 2).is_err());
+    }
+
+    #[test]
+    fn segment_keeps_led_places() {
+        let layout = Layout::new(&default_conf(), 29, 15, 7).unwrap();
+        let positions = layout.grid_positions();
+
+        let segment = layout.segment(10, 5, false);
+        assert_eq!(segment.len(), 5);
+        assert_eq!(segment.grid_size(), layout.grid_size());
+        assert_eq!(segment.grid_positions(), positions[10..15]);
+
+        let reversed: Vec<_> = positions[10..15].iter().rev().copied().collect();
+        assert_eq!(layout.segment(10, 5, true).grid_positions(), reversed);
+    }
+
+    #[test]
+    fn fingerprint_follows_led_places() {
+        let layout = Layout::new(&default_conf(), 29, 15, 7).unwrap();
+        assert_eq!(
+            layout.fingerprint(),
+            Layout::new(&default_conf(), 29, 15, 7)
+                .unwrap()
+                .fingerprint()
+        );
+
+        let mut reversed = default_conf();
+        reversed.direction = Direction::Clockwise;
+        for other in [
+            Layout::new(&default_conf(), 29, 15, 5).unwrap(),
+            Layout::new(&reversed, 29, 15, 7).unwrap(),
+            layout.segment(0, layout.len() - 1, false),
+        ] {
+            assert_ne!(layout.fingerprint(), other.fingerprint());
+        }
+    }
+}
@@ -4,29 +4,37 @@ use image::Pixel;
 use image::Rgb;
 use unit_interval::UnitInterval;
 
-use crate::config::CONFIG;
+use crate::config::TintConf;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct LedColor(Rgb<f32>);
 
 impl LedColor {
-    pub fn apply_tint(self) -> [u8; 3] {
+    pub fn apply_tint(self, tint: &TintConf) -> [u8; 3] {
         let rgb = self.0 .0;
 
-        let r = self._adjust_gamma(rgb[0], CONFIG.strip.tint.gamma[0]);
-        let g = self._adjust_gamma(rgb[1], CONFIG.strip.tint.gamma[1]);
-        let b = self._adjust_gamma(rgb[2], CONFIG.strip.tint.gamma[2]);
+        let r = self._adjust_gamma(rgb[0], tint.gamma[0]);
+        let g = self._adjust_gamma(rgb[1], tint.gamma[1]);
+        let b = self._adjust_gamma(rgb[2], tint.gamma[2]);
 
-        let (r, g, b) = self._adjust_saturation(r, g, b, &CONFIG.strip.tint.saturation);
+        let (r, g, b) = self._adjust_saturation(r, g, b, &tint.saturation);
 
-        let g = self._adjust_brightness(g, CONFIG.strip.tint.brightness[1]);
-        let b = self._adjust_brightness(b, CONFIG.strip.tint.brightness[2]);
+        let g = self._adjust_brightness(g, tint.brightness[1]);
+        let b = self._adjust_brightness(b, tint.brightness[2]);
 
-        let (r, g, b) = self._adjust_order(r, g, b);
+        let (r, g, b) = self._adjust_order(r, g, b, &tint.order);
 
         [r as u8, g as u8, b as u8]
     }
 
+    pub fn to_rgb8(self) -> [u8; 3] {
+        self.0 .0.map(|x| x.clamp(0.0, 255.0).round() as u8)
+    }
+
+    pub fn channels(self) -> [f32; 3] {
+        self.0 .0
+    }
+
     fn _adjust_gamma(&self, value: f32, gamma: f32) -> f32 {
         let normalized = value / 255.0;
         let corrected = normalized.powf(1.0 / gamma);
@@ -49,8 +57,8 @@ impl LedColor {
         (brightness * value).clamp(0.0, 255.0).round()
     }
 
-    fn _adjust_order(&self, r: f32, g: f32, b: f32) -> (f32, f32, f32) {
-        match CONFIG.strip.tint.order.as_str() {
+    fn _adjust_order(&self, r: f32, g: f32, b: f32, order: &str) -> (f32, f32, f32) {
+        match order {
             "RGB" => (r, g, b),
             "GRB" => (g, r, b),
             "BRG" => (b, r, g),
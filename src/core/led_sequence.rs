@@ -40,6 +40,20 @@ impl LedSequence {
         self
     }
 
+    pub fn reverse(&mut self) {
+        self.led_colors.reverse();
+    }
+
+    /// Moves every color `offset` leds forward, wrapping around the end
+    pub fn rotate(&mut self, offset: isize) {
+        if self.led_colors.is_empty() {
+            return;
+        }
+
+        let shift = offset.rem_euclid(self.led_colors.len() as isize) as usize;
+        self.led_colors.rotate_right(shift);
+    }
+
     // Default
 
     pub fn len(&self) -> usize {
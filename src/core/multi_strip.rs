@@ -0,0 +1,191 @@
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::config::StripConf;
+use crate::core::layout::Layout;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::{Strip, StripMod};
+use crate::errors::PLightError::{NestedMultiStrip, WrongLength, WrongSegment};
+
+/// One physical strip fed from a segment of the full LED sequence. Unset fields fall back to
+/// the `[strip]` section, overlapping segments duplicate the same LEDs on several strips.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct OutputConf {
+    pub mode: StripMod,
+
+    /// First LED of the segment
+    #[serde(default)]
+    pub start: usize,
+    /// Segment length, defaults to the rest of the sequence
+    pub length: Option<usize>,
+    /// Send the segment in reverse order
+    #[serde(default)]
+    pub reversed: bool,
+
+    pub serial_port: Option<String>,
+    pub baudrate: Option<u32>,
+    pub host: Option<String>,
+    pub universe: Option<u16>,
+
+    pub order: Option<String>,
+    pub gamma: Option<[f32; 3]>,
+    pub saturation: Option<[f32; 3]>,
+    pub brightness: Option<[f32; 3]>,
+}
+
+impl OutputConf {
+    fn strip_conf(&self, base: &StripConf) -> StripConf {
+        let mut conf = base.clone();
+
+        if let Some(serial_port) = &self.serial_port {
+            conf.arduino.serial_port = serial_port.clone();
+        }
+        if let Some(baudrate) = self.baudrate {
+            conf.arduino.baudrate = baudrate;
+        }
+        if let Some(host) = &self.host {
+            conf.wled.host = host.clone();
+            conf.dmx.host = Some(host.clone());
+            conf.ddp.host = host.clone();
+            conf.openrgb.host = host.clone();
+            conf.hyperion.host = host.clone();
+        }
+        if let Some(universe) = self.universe {
+            conf.dmx.universe = universe;
+        }
+
+        if let Some(order) = &self.order {
+            conf.tint.order = order.clone();
+        }
+        if let Some(gamma) = self.gamma {
+            conf.tint.gamma = gamma;
+        }
+        if let Some(saturation) = self.saturation {
+            conf.tint.saturation = saturation;
+        }
+        if let Some(brightness) = self.brightness {
+            conf.tint.brightness = brightness;
+        }
+
+        conf
+    }
+}
+
+struct Segment {
+    start: usize,
+    length: usize,
+    reversed: bool,
+}
+
+impl Segment {
+    fn slice(&self, led_colors: &LedSequence) -> LedSequence {
+        let indices = self.start..self.start + self.length;
+        let colors = |i: usize| led_colors.get(i).copied().unwrap_or_default();
+
+        if self.reversed {
+            indices.rev().map(colors).collect()
+        } else {
+            indices.map(colors).collect()
+        }
+    }
+}
+
+pub struct MultiStrip {
+    outputs: Vec<(Segment, Box<dyn Strip>)>,
+    strip_length: usize,
+}
+
+impl MultiStrip {
+    pub fn new(conf: &StripConf, layout: &Layout, strip_length: usize) -> Result<Self> {
+        let mut outputs = Vec::with_capacity(conf.outputs.len());
+
+        for output in &conf.outputs {
+            if output.mode == StripMod::Multi {
+                return Err(NestedMultiStrip.into());
+            }
+
+            let segment = Segment {
+                start: output.start,
+                length: output
+                    .length
+                    .unwrap_or(strip_length.saturating_sub(output.start)),
+                reversed: output.reversed,
+            };
+
+            if segment.length == 0 || segment.start + segment.length > strip_length {
+                return Err(WrongSegment {
+                    start: segment.start,
+                    length: segment.length,
+                    actual: strip_length,
+                }
+                .into());
+            }
+
+            info!(
+                "Output {:?} gets leds {}..{}{}",
+                output.mode,
+                segment.start,
+                segment.start + segment.length,
+                if segment.reversed { " reversed" } else { "" }
+            );
+
+            // Layout-aware outputs such as Terminal draw their LEDs where they really are
+            let layout = layout.segment(segment.start, segment.length, segment.reversed);
+            let strip = output
+                .mode
+                .get_strip(&output.strip_conf(conf), &layout, segment.length)?;
+            outputs.push((segment, strip));
+        }
+
+        Ok(MultiStrip {
+            outputs,
+            strip_length,
+        })
+    }
+
+    /// Sends the whole sequence to every strip
+    pub fn tee(strips: Vec<Box<dyn Strip>>, strip_length: usize) -> Self {
+        let outputs = strips
+            .into_iter()
+            .map(|strip| {
+                let segment = Segment {
+                    start: 0,
+                    length: strip_length,
+                    reversed: false,
+                };
+                (segment, strip)
+            })
+            .collect();
+
+        MultiStrip {
+            outputs,
+            strip_length,
+        }
+    }
+}
+
+impl Strip for MultiStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        // One failing strip should not freeze the others, so the first error is kept for later
+        let mut result = Ok(());
+        for (segment, strip) in &self.outputs {
+            if let Err(e) = strip.set_leds(&segment.slice(led_colors)) {
+                error!("Output error: {}", e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,544 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use anyhow::Result;
+use confique::Config;
+use log::info;
+
+use crate::config::TintConf;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+use crate::errors::PLightError::{OpenRgbMalformed, OpenRgbNotFound, WrongLength};
+
+const MAGIC: &[u8; 4] = b"ORGB";
+/// Newest protocol version whose controller data layout is understood
+const PROTOCOL_VERSION: u32 = 3;
+
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const REQUEST_CONTROLLER_DATA: u32 = 1;
+const REQUEST_PROTOCOL_VERSION: u32 = 40;
+const SET_CLIENT_NAME: u32 = 50;
+const RGBCONTROLLER_UPDATEZONELEDS: u32 = 1051;
+const RGBCONTROLLER_SETCUSTOMMODE: u32 = 1100;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct OpenRgbStripConf {
+    #[config(default = "127.0.0.1")]
+    pub host: String,
+    #[config(default = 6742)]
+    pub port: u16,
+
+    /// Part of the controller name, the first controller is used when unset
+    pub controller: Option<String>,
+    /// Part of the zone name, the first zone is used when unset
+    pub zone: Option<String>,
+}
+
+pub struct OpenRgbStrip {
+    stream: TcpStream,
+    controller: u32,
+    zone: u32,
+    zone_length: usize,
+    tint: TintConf,
+    strip_length: usize,
+}
+
+impl OpenRgbStrip {
+    pub fn new(conf: &OpenRgbStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let mut stream = TcpStream::connect((conf.host.as_str(), conf.port))?;
+        stream.set_read_timeout(Some(Duration::from_millis(1000)))?;
+
+        send(&mut stream, 0, SET_CLIENT_NAME, b"PLight\0")?;
+
+        send(
+            &mut stream,
+            0,
+            REQUEST_PROTOCOL_VERSION,
+            &PROTOCOL_VERSION.to_le_bytes(),
+        )?;
+        // Servers older than protocol version 1 do not answer at all
+        let server_version = match receive(&mut stream, REQUEST_PROTOCOL_VERSION) {
+            Ok(data) => Reader::new(&data).u32()?,
+            Err(e) if is_timeout(&e) => 0,
+            Err(e) => return Err(e),
+        };
+        let version = server_version.min(PROTOCOL_VERSION);
+
+        send(&mut stream, 0, REQUEST_CONTROLLER_COUNT, &[])?;
+        let count = Reader::new(&receive(&mut stream, REQUEST_CONTROLLER_COUNT)?).u32()?;
+
+        let mut target = None;
+        for index in 0..count {
+            send(
+                &mut stream,
+                index,
+                REQUEST_CONTROLLER_DATA,
+                &version.to_le_bytes(),
+            )?;
+            let controller =
+                parse_controller(&receive(&mut stream, REQUEST_CONTROLLER_DATA)?, version)?;
+
+            info!("OpenRGB controller {}: \"{}\"", index, controller.name);
+            for (zone_index, (zone, leds)) in controller.zones.iter().enumerate() {
+                info!("    zone {}: \"{}\" ({} leds)", zone_index, zone, leds);
+            }
+
+            if target.is_some() || !matches(&controller.name, &conf.controller) {
+                continue;
+            }
+
+            target = controller
+                .zones
+                .iter()
+                .position(|(zone, _)| matches(zone, &conf.zone))
+                .map(|zone| (index, zone as u32, controller.zones[zone].1 as usize));
+        }
+
+        let Some((controller, zone, zone_length)) = target else {
+            return Err(OpenRgbNotFound {
+                controller: conf.controller.clone().unwrap_or_default(),
+                zone: conf.zone.clone().unwrap_or_default(),
+            }
+            .into());
+        };
+
+        info!(
+            "Using OpenRGB controller {} zone {} ({} leds)",
+            controller, zone, zone_length
+        );
+
+        // Direct control needs the custom mode
+        send(&mut stream, controller, RGBCONTROLLER_SETCUSTOMMODE, &[])?;
+
+        Ok(OpenRgbStrip {
+            stream,
+            controller,
+            zone,
+            zone_length,
+            tint: tint.clone(),
+            strip_length,
+        })
+    }
+}
+
+impl Strip for OpenRgbStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let size = 4 + 4 + 2 + self.zone_length * 4;
+        let mut data = Vec::with_capacity(size);
+        data.extend_from_slice(&(size as u32).to_le_bytes());
+        data.extend_from_slice(&self.zone.to_le_bytes());
+        data.extend_from_slice(&(self.zone_length as u16).to_le_bytes());
+
+        // Zones rarely have the same length as the strip, so the sequence is stretched over them
+        for i in 0..self.zone_length {
+            let index = i * self.strip_length / self.zone_length.max(1);
+            let [r, g, b] = led_colors
+                .get(index)
+                .copied()
+                .unwrap_or_default()
+                .apply_tint(&self.tint);
+            data.extend_from_slice(&[r, g, b, 0]);
+        }
+
+        send(
+            &mut &self.stream,
+            self.controller,
+            RGBCONTROLLER_UPDATEZONELEDS,
+            &data,
+        )
+    }
+}
+
+struct Controller {
+    name: String,
+    /// Zone names with their LED counts
+    zones: Vec<(String, u32)>,
+}
+
+fn matches(name: &str, filter: &Option<String>) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|filter| name.to_lowercase().contains(&filter.to_lowercase()))
+}
+
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        )
+    })
+}
+
+fn send(stream: &mut impl Write, device: u32, id: u32, data: &[u8]) -> Result<()> {
+    let mut packet = Vec::with_capacity(16 + data.len());
+    packet.extend_from_slice(MAGIC);
+    packet.extend_from_slice(&device.to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    packet.extend_from_slice(data);
+
+    stream.write_all(&packet)?;
+    Ok(())
+}
+
+/// Reads packets until the reply to `id`, skipping server notifications
+fn receive(stream: &mut impl Read, id: u32) -> Result<Vec<u8>> {
+    loop {
+        let mut header = [0; 16];
+        stream.read_exact(&mut header)?;
+
+        let mut reader = Reader::new(&header);
+        if reader.take(4)? != MAGIC {
+            return Err(OpenRgbMalformed.into());
+        }
+        reader.u32()?;
+        let packet_id = reader.u32()?;
+        let size = reader.u32()? as usize;
+
+        let mut data = vec![0; size];
+        stream.read_exact(&mut data)?;
+
+        if packet_id == id {
+            return Ok(data);
+        }
+    }
+}
+
+/// Parses the controller description up to its zones
+fn parse_controller(data: &[u8], version: u32) -> Result<Controller> {
+    let mut reader = Reader::new(data);
+
+    reader.u32()?; // data size
+    reader.u32()?; // type
+    let name = reader.string()?;
+    if version >= 1 {
+        reader.string()?; // vendor
+    }
+    for _ in 0..4 {
+        reader.string()?; // description, version, serial, location
+    }
+
+    let num_modes = reader.u16()?;
+    reader.u32()?; // active mode
+    for _ in 0..num_modes {
+        reader.string()?;
+        // value, flags, speed min/max, [brightness min/max], colors min/max, speed,
+        // [brightness], direction, color mode
+        let fields = if version >= 3 { 12 } else { 9 };
+        reader.take(fields * 4)?;
+        let num_colors = reader.u16()? as usize;
+        reader.take(num_colors * 4)?;
+    }
+
+    let num_zones = reader.u16()?;
+    let mut zones = Vec::with_capacity(num_zones as usize);
+    for _ in 0..num_zones {
+        let zone = reader.string()?;
+        reader.u32()?; // type
+        reader.u32()?; // leds min
+        reader.u32()?; // leds max
+        let leds = reader.u32()?;
+        let matrix_size = reader.u16()? as usize;
+        reader.take(matrix_size)?;
+        zones.push((zone, leds));
+    }
+
+    Ok(Controller { name, zones })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(OpenRgbMalformed)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Length prefixed and null terminated string
+    fn string(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        Ok(String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+    use crate::core::led_color::LedColor;
+    use crate::errors::PLightError;
+
+    const DEVICE_LIST_UPDATED: u32 = 100;
+
+    struct MockZone {
+        name: &'static str,
+        leds: u32,
+        /// Height and width of the zone matrix
+        matrix: Option<(u32, u32)>,
+    }
+
+    struct MockController {
+        name: &'static str,
+        zones: Vec<MockZone>,
+    }
+
+    /// Packets received by the mock server as (device, id, data)
+    type Received = Vec<(u32, u32, Vec<u8>)>;
+
+    fn put_string(data: &mut Vec<u8>, value: &str) {
+        data.extend_from_slice(&(value.len() as u16 + 1).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+    }
+
+    /// Controller data block in the layout of the given protocol version
+    fn encode_controller(controller: &MockController, version: u32) -> Vec<u8> {
+        let mut data = vec![0; 4];
+        data.extend_from_slice(&2_u32.to_le_bytes());
+        put_string(&mut data, controller.name);
+        if version >= 1 {
+            put_string(&mut data, "Vendor");
+        }
+        for field in ["Description", "1.0", "SN42", "HID: /dev/hidraw0"] {
+            put_string(&mut data, field);
+        }
+
+        data.extend_from_slice(&2_u16.to_le_bytes());
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        for mode in ["Direct", "Static"] {
+            put_string(&mut data, mode);
+            let fields = if version >= 3 { 12 } else { 9 };
+            data.extend(std::iter::repeat_n(0xab, fields * 4));
+            data.extend_from_slice(&2_u16.to_le_bytes());
+            data.extend_from_slice(&[0xcd; 8]);
+        }
+
+        data.extend_from_slice(&(controller.zones.len() as u16).to_le_bytes());
+        for zone in &controller.zones {
+            put_string(&mut data, zone.name);
+            for value in [1, 0, zone.leds, zone.leds] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            match zone.matrix {
+                Some((height, width)) => {
+                    let cells = height * width;
+                    data.extend_from_slice(&(8 + cells as u16 * 4).to_le_bytes());
+                    data.extend_from_slice(&height.to_le_bytes());
+                    data.extend_from_slice(&width.to_le_bytes());
+                    data.extend(std::iter::repeat_n(0xef, cells as usize * 4));
+                }
+                None => data.extend_from_slice(&0_u16.to_le_bytes()),
+            }
+        }
+
+        // LEDs and colors follow, the strip never reads them
+        data.extend_from_slice(&0_u16.to_le_bytes());
+        data.extend_from_slice(&0_u16.to_le_bytes());
+
+        let size = data.len() as u32;
+        data[..4].copy_from_slice(&size.to_le_bytes());
+        data
+    }
+
+    /// Serves one client like an OpenRGB server speaking `server_version`, until it hangs up
+    fn serve(server_version: u32) -> (u16, JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let controllers = [
+            MockController {
+                name: "Corsair K70",
+                zones: vec![MockZone {
+                    name: "Keyboard",
+                    leds: 104,
+                    matrix: Some((6, 22)),
+                }],
+            },
+            MockController {
+                name: "ASUS Aura Motherboard",
+                zones: vec![
+                    MockZone {
+                        name: "Aura Mainboard",
+                        leds: 6,
+                        matrix: Some((2, 3)),
+                    },
+                    MockZone {
+                        name: "Aura Addressable 1",
+                        leds: 20,
+                        matrix: None,
+                    },
+                ],
+            },
+        ];
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+
+            loop {
+                let mut header = [0; 16];
+                if stream.read_exact(&mut header).is_err() {
+                    return received;
+                }
+                assert_eq!(&header[..4], MAGIC);
+                let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+                let (device, id, size) = (field(4), field(8), field(12));
+
+                let mut data = vec![0; size as usize];
+                stream.read_exact(&mut data).unwrap();
+
+                match id {
+                    // Servers before version 1 do not know this request
+                    REQUEST_PROTOCOL_VERSION if server_version > 0 => {
+                        send(&mut stream, 0, id, &server_version.to_le_bytes()).unwrap()
+                    }
+                    REQUEST_CONTROLLER_COUNT => {
+                        send(&mut stream, 0, DEVICE_LIST_UPDATED, &[]).unwrap();
+                        send(
+                            &mut stream,
+                            0,
+                            id,
+                            &(controllers.len() as u32).to_le_bytes(),
+                        )
+                        .unwrap();
+                    }
+                    REQUEST_CONTROLLER_DATA => {
+                        let version = u32::from_le_bytes(data[..4].try_into().unwrap());
+                        let controller = encode_controller(&controllers[device as usize], version);
+                        send(&mut stream, device, id, &controller).unwrap();
+                    }
+                    _ => {}
+                }
+
+                received.push((device, id, data));
+            }
+        });
+
+        (port, handle)
+    }
+
+    fn conf(port: u16, controller: &str, zone: &str) -> OpenRgbStripConf {
+        OpenRgbStripConf {
+            host: "127.0.0.1".into(),
+            port,
+            controller: Some(controller.into()),
+            zone: Some(zone.into()),
+        }
+    }
+
+    fn drive(server_version: u32, expected_version: u32) {
+        let (port, server) = serve(server_version);
+
+        let strip = OpenRgbStrip::new(
+            &conf(port, "aura", "ADDRESSABLE"),
+            &TintConf::identity(),
+            10,
+        )
+        .unwrap();
+        let led_sequence: LedSequence = (0..10_u8)
+            .map(|i| LedColor::from([i * 20, 7, 255 - i]))
+            .collect();
+        strip.set_leds(&led_sequence).unwrap();
+        drop(strip);
+
+        let received = server.join().unwrap();
+        let ids: Vec<u32> = received.iter().map(|(_, id, _)| *id).collect();
+        assert_eq!(
+            ids,
+            [
+                SET_CLIENT_NAME,
+                REQUEST_PROTOCOL_VERSION,
+                REQUEST_CONTROLLER_COUNT,
+                REQUEST_CONTROLLER_DATA,
+                REQUEST_CONTROLLER_DATA,
+                RGBCONTROLLER_SETCUSTOMMODE,
+                RGBCONTROLLER_UPDATEZONELEDS,
+            ]
+        );
+        assert_eq!(received[0].2, b"PLight\0");
+        for (_, _, data) in &received[3..5] {
+            assert_eq!(data, &expected_version.to_le_bytes());
+        }
+        assert_eq!(received[5].0, 1);
+
+        let (device, _, payload) = &received[6];
+        assert_eq!(*device, 1);
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&90_u32.to_le_bytes());
+        expected.extend_from_slice(&1_u32.to_le_bytes());
+        expected.extend_from_slice(&20_u16.to_le_bytes());
+        for i in 0..20_u8 {
+            let led = i / 2;
+            expected.extend_from_slice(&[led * 20, 7, 255 - led, 0]);
+        }
+        assert_eq!(payload, &expected);
+    }
+
+    #[test]
+    fn server_without_version_request() {
+        drive(0, 0);
+    }
+
+    #[test]
+    fn server_with_current_version() {
+        drive(3, 3);
+    }
+
+    #[test]
+    fn newer_server_is_spoken_to_in_known_version() {
+        drive(4, 3);
+    }
+
+    #[test]
+    fn missing_zone_is_reported() {
+        let (port, server) = serve(3);
+
+        let err = OpenRgbStrip::new(
+            &conf(port, "corsair", "addressable"),
+            &TintConf::identity(),
+            10,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<PLightError>(),
+            Some(PLightError::OpenRgbNotFound { .. })
+        ));
+
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Result;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, Rgba, RgbaImage};
+use log::{error, info};
+
+use crate::core::layout::Layout;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+use crate::errors::PLightError::WrongLength;
+
+/// Side of one LED square in the recorded picture (pixels)
+const CELL_SIZE: u32 = 16;
+const BACKGROUND: Rgba<u8> = Rgba([24, 24, 24, 255]);
+
+enum Output {
+    Gif(Box<GifEncoder<File>>),
+    /// Directory receiving numbered PNG frames
+    Png(PathBuf),
+}
+
+struct Recorder {
+    output: Output,
+    frames: usize,
+    last_frame: Option<(RgbaImage, Instant)>,
+}
+
+impl Recorder {
+    /// Writes the pending GIF frame, it stayed on the strip until now
+    fn flush(&mut self) -> Result<()> {
+        if let (Output::Gif(encoder), Some((previous, shown_at))) =
+            (&mut self.output, self.last_frame.take())
+        {
+            let delay = Delay::from_saturating_duration(shown_at.elapsed());
+            encoder.encode_frame(Frame::from_parts(previous, 0, 0, delay))?;
+        }
+        Ok(())
+    }
+}
+
+/// Records every frame as a picture of the strip around the screen rectangle. A path ending
+/// in `.gif` becomes an animated GIF, anything else a directory of numbered PNG files.
+pub struct RecordingStrip {
+    recorder: Mutex<Recorder>,
+    positions: Vec<Option<(usize, usize)>>,
+    columns: usize,
+    rows: usize,
+    strip_length: usize,
+}
+
+impl RecordingStrip {
+    pub fn new(path: &Path, layout: &Layout, strip_length: usize) -> Result<Self> {
+        let output = if path.extension().is_some_and(|extension| extension == "gif") {
+            let mut encoder = GifEncoder::new(File::create(path)?);
+            encoder.set_repeat(Repeat::Infinite)?;
+            Output::Gif(Box::new(encoder))
+        } else {
+            fs::create_dir_all(path)?;
+            Output::Png(path.to_path_buf())
+        };
+
+        info!("Recording frames to {}", path.display());
+
+        let (columns, rows) = layout.grid_size();
+
+        Ok(RecordingStrip {
+            recorder: Mutex::new(Recorder {
+                output,
+                frames: 0,
+                last_frame: None,
+            }),
+            positions: layout.grid_positions(),
+            columns,
+            rows,
+            strip_length,
+        })
+    }
+
+    fn draw(&self, led_colors: &LedSequence) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(
+            self.columns as u32 * CELL_SIZE,
+            self.rows as u32 * CELL_SIZE,
+            BACKGROUND,
+        );
+
+        for (led_color, position) in led_colors.into_iter().zip(&self.positions) {
+            let Some((column, row)) = position else {
+                continue;
+            };
+            let [r, g, b] = led_color.to_rgb8();
+            for y in 1..CELL_SIZE - 1 {
+                for x in 1..CELL_SIZE - 1 {
+                    image.put_pixel(
+                        *column as u32 * CELL_SIZE + x,
+                        *row as u32 * CELL_SIZE + y,
+                        Rgba([r, g, b, 255]),
+                    );
+                }
+            }
+        }
+
+        image
+    }
+}
+
+impl Strip for RecordingStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let image = self.draw(led_colors);
+        let mut recorder = self.recorder.lock().unwrap();
+
+        match &recorder.output {
+            Output::Png(directory) => {
+                image.save(directory.join(format!("frame_{:06}.png", recorder.frames)))?;
+            }
+            Output::Gif(_) => {
+                // A GIF frame is only written once the next one arrives, as its delay is the
+                // time it stayed on the strip. Identical frames just stretch that delay.
+                if recorder
+                    .last_frame
+                    .as_ref()
+                    .is_some_and(|(previous, _)| *previous == image)
+                {
+                    return Ok(());
+                }
+                recorder.flush()?;
+                recorder.last_frame = Some((image, Instant::now()));
+            }
+        }
+
+        recorder.frames += 1;
+        Ok(())
+    }
+}
+
+impl Drop for RecordingStrip {
+    /// The last frame is still pending, without it a static scene would give an empty GIF
+    fn drop(&mut self) {
+        if let Ok(recorder) = self.recorder.get_mut() {
+            if let Err(e) = recorder.flush() {
+                error!("Can not write the last recorded frame: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::BufReader;
+    use std::process;
+
+    use confique::Config;
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    use super::*;
+    use crate::core::layout::LayoutConf;
+    use crate::core::led_color::LedColor;
+
+    /// Records one frame per color into a GIF, drops the strip and decodes what was written
+    fn record(name: &str, colors: &[[u8; 3]]) -> (Vec<Frame>, (usize, usize)) {
+        let path = env::temp_dir().join(format!("plight_{}_{}.gif", name, process::id()));
+        let layout = Layout::new(&LayoutConf::builder().load().unwrap(), 6, 4, 0).unwrap();
+        let position = layout.grid_positions()[0].unwrap();
+
+        let strip = RecordingStrip::new(&path, &layout, layout.len()).unwrap();
+        for &rgb in colors {
+            let led_sequence: LedSequence =
+                (0..layout.len()).map(|_| LedColor::from(rgb)).collect();
+            strip.set_leds(&led_sequence).unwrap();
+        }
+        drop(strip);
+
+        let file = BufReader::new(File::open(&path).unwrap());
+        let frames = GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        (frames, position)
+    }
+
+    fn led_pixel(frame: &Frame, (column, row): (usize, usize)) -> [u8; 3] {
+        let Rgba([r, g, b, _]) = *frame.buffer().get_pixel(
+            column as u32 * CELL_SIZE + CELL_SIZE / 2,
+            row as u32 * CELL_SIZE + CELL_SIZE / 2,
+        );
+        [r, g, b]
+    }
+
+    #[test]
+    fn static_scene_is_written_on_drop() {
+        let (frames, position) = record("static", &[[200, 0, 0]; 3]);
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(led_pixel(&frames[0], position), [200, 0, 0]);
+    }
+
+    #[test]
+    fn every_change_becomes_a_frame() {
+        let colors = [[200, 0, 0], [200, 0, 0], [0, 0, 200], [200, 0, 0]];
+        let (frames, position) = record("changes", &colors);
+
+        let shown: Vec<[u8; 3]> = frames
+            .iter()
+            .map(|frame| led_pixel(frame, position))
+            .collect();
+        assert_eq!(shown, [[200, 0, 0], [0, 0, 200], [200, 0, 0]]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use anyhow::{Context, Result};
+use rand::random;
+
+use crate::config::TintConf;
+use crate::core::dmx::{split_into_universes, DmxStripConf};
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+
+const SACN_PORT: u16 = 5568;
+const ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+
+pub struct SacnStrip {
+    socket: UdpSocket,
+    unicast: Option<SocketAddr>,
+    cid: [u8; 16],
+    conf: DmxStripConf,
+    tint: TintConf,
+    strip_length: usize,
+    sequence: AtomicU8,
+}
+
+impl SacnStrip {
+    pub fn new(conf: &DmxStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let unicast = match &conf.host {
+            Some(host) => Some(
+                (host.as_str(), SACN_PORT)
+                    .to_socket_addrs()?
+                    .next()
+                    .with_context(|| format!("Can not resolve sACN host {}", host))?,
+            ),
+            None => None,
+        };
+
+        Ok(SacnStrip {
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            unicast,
+            cid: random_cid(),
+            conf: conf.clone(),
+            tint: tint.clone(),
+            strip_length,
+            sequence: AtomicU8::new(0),
+        })
+    }
+}
+
+impl Strip for SacnStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        let universes = split_into_universes(
+            led_colors,
+            &self.tint,
+            self.strip_length,
+            self.conf.universe,
+            self.conf.channel_offset,
+        )?;
+
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        for (universe, data) in universes {
+            let packet = encode_packet(
+                &self.cid,
+                &self.conf.source_name,
+                self.conf.priority,
+                sequence,
+                universe,
+                &data,
+            );
+            let destination = self.unicast.unwrap_or_else(|| multicast_address(universe));
+            self.socket.send_to(&packet, destination)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn multicast_address(universe: u16) -> SocketAddr {
+    let [hi, lo] = universe.to_be_bytes();
+    SocketAddr::from((Ipv4Addr::new(239, 255, hi, lo), SACN_PORT))
+}
+
+/// Random version 4 UUID, receivers tell sources apart by this component identifier
+fn random_cid() -> [u8; 16] {
+    let mut cid: [u8; 16] = random();
+    cid[6] = (cid[6] & 0x0f) | 0x40;
+    cid[8] = (cid[8] & 0x3f) | 0x80;
+    cid
+}
+
+/// Builds an E1.31 data packet carrying `data` as DMX slots with the null start code
+pub fn encode_packet(
+    cid: &[u8; 16],
+    source_name: &str,
+    priority: u8,
+    sequence: u8,
+    universe: u16,
+    data: &[u8],
+) -> Vec<u8> {
+    let slots = data.len();
+    let mut packet = Vec::with_capacity(126 + slots);
+
+    // Root layer
+    packet.extend_from_slice(&0x0010_u16.to_be_bytes());
+    packet.extend_from_slice(&0x0000_u16.to_be_bytes());
+    packet.extend_from_slice(ACN_PACKET_IDENTIFIER);
+    packet.extend_from_slice(&flags_and_length(110 + slots));
+    packet.extend_from_slice(&VECTOR_ROOT_E131_DATA.to_be_bytes());
+    packet.extend_from_slice(cid);
+
+    // Framing layer
+    packet.extend_from_slice(&flags_and_length(88 + slots));
+    packet.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+    let mut name = [0_u8; 64];
+    let name_len = source_name.len().min(63);
+    name[..name_len].copy_from_slice(&source_name.as_bytes()[..name_len]);
+    packet.extend_from_slice(&name);
+    packet.push(priority.min(200));
+    packet.extend_from_slice(&0_u16.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.extend_from_slice(&universe.to_be_bytes());
+
+    // DMP layer
+    packet.extend_from_slice(&flags_and_length(11 + slots));
+    packet.push(VECTOR_DMP_SET_PROPERTY);
+    packet.push(0xa1);
+    packet.extend_from_slice(&0_u16.to_be_bytes());
+    packet.extend_from_slice(&1_u16.to_be_bytes());
+    packet.extend_from_slice(&(slots as u16 + 1).to_be_bytes());
+    packet.push(0);
+    packet.extend_from_slice(data);
+
+    packet
+}
+
+fn flags_and_length(length: usize) -> [u8; 2] {
+    (0x7000 | length as u16).to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn be16(packet: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([packet[at], packet[at + 1]])
+    }
+
+    fn be32(packet: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(packet[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn data_packet_layout() {
+        let cid = [0x42; 16];
+        let data = [10, 20, 30, 40, 50, 60];
+        let packet = encode_packet(&cid, "PLight", 250, 9, 0x0102, &data);
+
+        assert_eq!(packet.len(), 126 + data.len());
+
+        // Root layer
+        assert_eq!(be16(&packet, 0), 0x0010);
+        assert_eq!(be16(&packet, 2), 0);
+        assert_eq!(&packet[4..16], ACN_PACKET_IDENTIFIER);
+        assert_eq!(be16(&packet, 16), 0x7000 | (packet.len() - 16) as u16);
+        assert_eq!(be32(&packet, 18), VECTOR_ROOT_E131_DATA);
+        assert_eq!(packet[22..38], cid);
+
+        // Framing layer
+        assert_eq!(be16(&packet, 38), 0x7000 | (packet.len() - 38) as u16);
+        assert_eq!(be32(&packet, 40), VECTOR_E131_DATA_PACKET);
+        assert_eq!(&packet[44..50], b"PLight");
+        assert!(packet[50..108].iter().all(|&byte| byte == 0));
+        assert_eq!(packet[108], 200);
+        assert_eq!(be16(&packet, 109), 0);
+        assert_eq!(packet[111], 9);
+        assert_eq!(packet[112], 0);
+        assert_eq!(be16(&packet, 113), 0x0102);
+
+        // DMP layer
+        assert_eq!(be16(&packet, 115), 0x7000 | (packet.len() - 115) as u16);
+        assert_eq!(packet[117], VECTOR_DMP_SET_PROPERTY);
+        assert_eq!(packet[118], 0xa1);
+        assert_eq!(be16(&packet, 119), 0);
+        assert_eq!(be16(&packet, 121), 1);
+        assert_eq!(be16(&packet, 123), data.len() as u16 + 1);
+        assert_eq!(packet[125], 0);
+        assert_eq!(packet[126..], data);
+    }
+
+    #[test]
+    fn long_source_name_is_truncated() {
+        let name = "x".repeat(100);
+        let packet = encode_packet(&[0; 16], &name, 100, 0, 1, &[]);
+
+        assert!(packet[44..107].iter().all(|&byte| byte == b'x'));
+        assert_eq!(packet[107], 0);
+    }
+
+    #[test]
+    fn cid_is_a_random_uuid() {
+        let (a, b) = (random_cid(), random_cid());
+
+        assert_ne!(a, b);
+        assert_eq!(a[6] >> 4, 4);
+        assert_eq!(a[8] >> 6, 0b10);
+    }
+
+    #[test]
+    fn multicast_address_follows_universe() {
+        assert_eq!(
+            multicast_address(0x0102),
+            SocketAddr::from((Ipv4Addr::new(239, 255, 1, 2), SACN_PORT))
+        );
+    }
+}
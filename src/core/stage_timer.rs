@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Accumulates how long a pipeline stage spends per frame and logs the average periodically
+pub struct StageTimer {
+    name: &'static str,
+    busy: Duration,
+    frames: u32,
+    since: Instant,
+}
+
+impl StageTimer {
+    pub fn new(name: &'static str) -> Self {
+        StageTimer {
+            name,
+            busy: Duration::ZERO,
+            frames: 0,
+            since: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, started: Instant) {
+        self.busy += started.elapsed();
+        self.frames += 1;
+
+        let elapsed = self.since.elapsed();
+        if elapsed >= REPORT_INTERVAL {
+            debug!(
+                "{} stage: {:.1} fps, {:.2} ms per frame",
+                self.name,
+                self.frames as f64 / elapsed.as_secs_f64(),
+                self.busy.as_secs_f64() * 1000.0 / self.frames as f64
+            );
+
+            self.busy = Duration::ZERO;
+            self.frames = 0;
+            self.since = Instant::now();
+        }
+    }
+}
@@ -1,10 +1,66 @@
-use crate::core::led_sequence::LedSequence;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-pub trait Strip {
-    fn new() -> Result<Self>
-    where
-        Self: Sized;
+use crate::config::StripConf;
+use crate::core::arduino_strip::ArduinoStrip;
+use crate::core::artnet_strip::ArtNetStrip;
+use crate::core::ddp_strip::DdpStrip;
+use crate::core::hyperion_strip::HyperionStrip;
+use crate::core::layout::Layout;
+use crate::core::led_sequence::LedSequence;
+use crate::core::multi_strip::MultiStrip;
+use crate::core::openrgb_strip::OpenRgbStrip;
+use crate::core::sacn_strip::SacnStrip;
+use crate::core::terminal_strip::TerminalStrip;
+use crate::core::wled_strip::WledStrip;
 
+pub trait Strip {
     fn set_leds(&self, led_colors: &LedSequence) -> Result<()>;
 }
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum StripMod {
+    Arduino,
+    Wled,
+    Sacn,
+    ArtNet,
+    Ddp,
+    OpenRgb,
+    Hyperion,
+    Terminal,
+    Multi,
+}
+
+impl StripMod {
+    pub fn get_strip(
+        &self,
+        conf: &StripConf,
+        layout: &Layout,
+        strip_length: usize,
+    ) -> Result<Box<dyn Strip>> {
+        let tint = &conf.tint;
+        match self {
+            StripMod::Arduino => Ok(Box::new(ArduinoStrip::new(
+                &conf.arduino,
+                tint,
+                strip_length,
+            )?)),
+            StripMod::Wled => Ok(Box::new(WledStrip::new(&conf.wled, tint, strip_length)?)),
+            StripMod::Sacn => Ok(Box::new(SacnStrip::new(&conf.dmx, tint, strip_length)?)),
+            StripMod::ArtNet => Ok(Box::new(ArtNetStrip::new(&conf.dmx, tint, strip_length)?)),
+            StripMod::Ddp => Ok(Box::new(DdpStrip::new(&conf.ddp, tint, strip_length)?)),
+            StripMod::OpenRgb => Ok(Box::new(OpenRgbStrip::new(
+                &conf.openrgb,
+                tint,
+                strip_length,
+            )?)),
+            StripMod::Hyperion => Ok(Box::new(HyperionStrip::new(
+                &conf.hyperion,
+                tint,
+                strip_length,
+            )?)),
+            StripMod::Terminal => Ok(Box::new(TerminalStrip::new(layout, strip_length)?)),
+            StripMod::Multi => Ok(Box::new(MultiStrip::new(conf, layout, strip_length)?)),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use crate::core::layout::Layout;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+use crate::errors::PLightError::WrongLength;
+
+/// Draws the strip as a live truecolor rectangle in the terminal, for working on sources and
+/// behaviors without hardware. Colors are shown before tinting.
+pub struct TerminalStrip {
+    /// Cell of every LED in the layout's grid, `None` for skipped LEDs
+    positions: Vec<Option<(usize, usize)>>,
+    columns: usize,
+    rows: usize,
+    strip_length: usize,
+    cleared: AtomicBool,
+}
+
+impl TerminalStrip {
+    pub fn new(layout: &Layout, strip_length: usize) -> Result<Self> {
+        let (columns, rows) = layout.grid_size();
+
+        Ok(TerminalStrip {
+            positions: layout.grid_positions(),
+            columns,
+            rows,
+            strip_length,
+            cleared: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Strip for TerminalStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let mut grid = vec![None; self.columns * self.rows];
+        for (led_color, position) in led_colors.into_iter().zip(&self.positions) {
+            let Some((column, row)) = position else {
+                continue;
+            };
+            grid[row * self.columns + column] = Some(led_color.to_rgb8());
+        }
+
+        let mut frame = String::new();
+        if !self.cleared.swap(true, Ordering::Relaxed) {
+            frame.push_str("\x1b[2J\x1b[?25l");
+        }
+        frame.push_str("\x1b[H");
+
+        for row in grid.chunks(self.columns) {
+            for cell in row {
+                match cell {
+                    Some([r, g, b]) => frame.push_str(&format!("\x1b[38;2;{};{};{}m██", r, g, b)),
+                    None => frame.push_str("\x1b[0m  "),
+                }
+            }
+            frame.push_str("\x1b[0m\n");
+        }
+
+        let mut stdout = stdout().lock();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalStrip {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h");
+    }
+}
@@ -0,0 +1,294 @@
+use std::net::UdpSocket;
+
+use anyhow::Result;
+use confique::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::config::TintConf;
+use crate::core::led_sequence::LedSequence;
+use crate::core::strip::Strip;
+use crate::errors::PLightError::{TooManyLeds, WrongLength};
+
+const WARLS_MAX_LEDS: usize = 255;
+const DRGB_MAX_LEDS: usize = 490;
+const DRGBW_MAX_LEDS: usize = 367;
+const DNRGB_MAX_LEDS: usize = 489;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct WledStripConf {
+    /// Address of the WLED device
+    #[config(default = "4.3.2.1")]
+    pub host: String,
+    #[config(default = 21324)]
+    pub port: u16,
+
+    /// Realtime protocol (Warls, Drgb, Drgbw, Dnrgb)
+    #[config(default = "Dnrgb")]
+    pub protocol: WledProtocol,
+
+    /// Seconds WLED waits for the next packet before returning to its own effects (255 = never)
+    #[config(default = 2)]
+    pub timeout: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum WledProtocol {
+    Warls,
+    Drgb,
+    Drgbw,
+    Dnrgb,
+}
+
+impl WledProtocol {
+    fn id(&self) -> u8 {
+        match self {
+            WledProtocol::Warls => 1,
+            WledProtocol::Drgb => 2,
+            WledProtocol::Drgbw => 3,
+            WledProtocol::Dnrgb => 4,
+        }
+    }
+
+    fn max_leds(&self) -> usize {
+        match self {
+            WledProtocol::Warls => WARLS_MAX_LEDS,
+            WledProtocol::Drgb => DRGB_MAX_LEDS,
+            WledProtocol::Drgbw => DRGBW_MAX_LEDS,
+            WledProtocol::Dnrgb => DNRGB_MAX_LEDS,
+        }
+    }
+}
+
+pub struct WledStrip {
+    socket: UdpSocket,
+    protocol: WledProtocol,
+    timeout: u8,
+    tint: TintConf,
+    strip_length: usize,
+}
+
+impl WledStrip {
+    pub fn new(conf: &WledStripConf, tint: &TintConf, strip_length: usize) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((conf.host.as_str(), conf.port))?;
+
+        Ok(WledStrip {
+            socket,
+            protocol: conf.protocol,
+            timeout: conf.timeout,
+            tint: tint.clone(),
+            strip_length,
+        })
+    }
+}
+
+impl Strip for WledStrip {
+    fn set_leds(&self, led_colors: &LedSequence) -> Result<()> {
+        if led_colors.len() != self.strip_length {
+            return Err(WrongLength {
+                given: led_colors.len(),
+                actual: self.strip_length,
+            }
+            .into());
+        }
+
+        let colors: Vec<[u8; 3]> = led_colors
+            .into_iter()
+            .map(|c| c.apply_tint(&self.tint))
+            .collect();
+
+        let packets = encode_packets(self.protocol, self.timeout, &colors)?;
+        for packet in packets {
+            self.socket.send(&packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the UDP packets for a whole frame. Only DNRGB carries a start index, so it is the
+/// only protocol that can split long strips into several packets.
+pub fn encode_packets(
+    protocol: WledProtocol,
+    timeout: u8,
+    colors: &[[u8; 3]],
+) -> Result<Vec<Vec<u8>>> {
+    if protocol != WledProtocol::Dnrgb && colors.len() > protocol.max_leds() {
+        return Err(TooManyLeds {
+            given: colors.len(),
+            max: protocol.max_leds(),
+        }
+        .into());
+    }
+
+    let header = [protocol.id(), timeout];
+
+    let packets = match protocol {
+        WledProtocol::Warls => {
+            let mut packet = header.to_vec();
+            for (i, [r, g, b]) in colors.iter().enumerate() {
+                packet.extend_from_slice(&[i as u8, *r, *g, *b]);
+            }
+            vec![packet]
+        }
+        WledProtocol::Drgb => {
+            let mut packet = header.to_vec();
+            packet.extend(colors.iter().flatten());
+            vec![packet]
+        }
+        WledProtocol::Drgbw => {
+            let mut packet = header.to_vec();
+            for [r, g, b] in colors {
+                let w = *r.min(g).min(b);
+                packet.extend_from_slice(&[r - w, g - w, b - w, w]);
+            }
+            vec![packet]
+        }
+        WledProtocol::Dnrgb => colors
+            .chunks(DNRGB_MAX_LEDS)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let start = (i * DNRGB_MAX_LEDS) as u16;
+                let mut packet = header.to_vec();
+                packet.extend_from_slice(&start.to_be_bytes());
+                packet.extend(chunk.iter().flatten());
+                packet
+            })
+            .collect(),
+    };
+
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::core::led_color::LedColor;
+    use crate::errors::PLightError;
+
+    fn colors(len: usize) -> Vec<[u8; 3]> {
+        (0..len)
+            .map(|i| [i as u8, (i * 7) as u8, 255 - i as u8])
+            .collect()
+    }
+
+    /// Strip sending to a socket bound on localhost, returned with that socket
+    fn strip(protocol: WledProtocol, strip_length: usize) -> (WledStrip, UdpSocket) {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let conf = WledStripConf {
+            host: "127.0.0.1".into(),
+            port: receiver.local_addr().unwrap().port(),
+            protocol,
+            timeout: 2,
+        };
+        let strip = WledStrip::new(&conf, &TintConf::identity(), strip_length).unwrap();
+
+        (strip, receiver)
+    }
+
+    fn send(strip: &WledStrip, colors: &[[u8; 3]]) -> Result<()> {
+        let led_sequence: LedSequence = colors.iter().map(|&rgb| LedColor::from(rgb)).collect();
+        strip.set_leds(&led_sequence)
+    }
+
+    fn receive(receiver: &UdpSocket) -> Vec<u8> {
+        let mut buf = [0; 2048];
+        let len = receiver.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn warls_packet_carries_an_index_per_led() {
+        let (strip, receiver) = strip(WledProtocol::Warls, 10);
+        let sent = colors(10);
+        send(&strip, &sent).unwrap();
+
+        let packet = receive(&receiver);
+        assert_eq!(&packet[..2], &[1, 2]);
+
+        let decoded: Vec<(u8, [u8; 3])> = packet[2..]
+            .chunks_exact(4)
+            .map(|led| (led[0], [led[1], led[2], led[3]]))
+            .collect();
+        let expected: Vec<(u8, [u8; 3])> = sent
+            .iter()
+            .enumerate()
+            .map(|(i, &rgb)| (i as u8, rgb))
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn drgb_packet_carries_plain_colors() {
+        let (strip, receiver) = strip(WledProtocol::Drgb, 20);
+        let sent = colors(20);
+        send(&strip, &sent).unwrap();
+
+        let packet = receive(&receiver);
+        assert_eq!(&packet[..2], &[2, 2]);
+        assert_eq!(packet[2..], *sent.concat());
+    }
+
+    #[test]
+    fn drgbw_packet_moves_common_part_to_white() {
+        let (strip, receiver) = strip(WledProtocol::Drgbw, 3);
+        send(&strip, &[[10, 20, 30], [255, 255, 255], [0, 5, 9]]).unwrap();
+
+        let packet = receive(&receiver);
+        assert_eq!(&packet[..2], &[3, 2]);
+        assert_eq!(packet[2..], [0, 10, 20, 10, 0, 0, 0, 255, 0, 5, 9, 0]);
+    }
+
+    #[test]
+    fn dnrgb_fits_one_packet_up_to_the_limit() {
+        let (strip, receiver) = strip(WledProtocol::Dnrgb, 489);
+        let sent = colors(489);
+        send(&strip, &sent).unwrap();
+
+        let packet = receive(&receiver);
+        assert_eq!(&packet[..4], &[4, 2, 0, 0]);
+        assert_eq!(packet[4..], *sent.concat());
+
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut [0; 16]).is_err());
+    }
+
+    #[test]
+    fn dnrgb_splits_longer_strips_with_start_index() {
+        let (strip, receiver) = strip(WledProtocol::Dnrgb, 490);
+        let sent = colors(490);
+        send(&strip, &sent).unwrap();
+
+        let first = receive(&receiver);
+        assert_eq!(&first[..4], &[4, 2, 0, 0]);
+        assert_eq!(first[4..], *sent[..489].concat());
+
+        let second = receive(&receiver);
+        assert_eq!(&second[..4], &[4, 2, 0x01, 0xE9]);
+        assert_eq!(second[4..], *sent[489..].concat());
+    }
+
+    #[test]
+    fn too_many_leds_for_protocol() {
+        for (protocol, max) in [
+            (WledProtocol::Warls, WARLS_MAX_LEDS),
+            (WledProtocol::Drgb, DRGB_MAX_LEDS),
+            (WledProtocol::Drgbw, DRGBW_MAX_LEDS),
+        ] {
+            assert!(encode_packets(protocol, 2, &colors(max)).is_ok());
+
+            let err = encode_packets(protocol, 2, &colors(max + 1)).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<PLightError>(),
+                Some(PLightError::TooManyLeds { given, max: m }) if *given == max + 1 && *m == max
+            ));
+        }
+    }
+}
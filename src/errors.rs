@@ -1,16 +1,51 @@
-use std::io;
-
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum PLightError {
     #[error("given {given}")]
     WrongWallpaperPath { given: String },
+    #[error("not a PLight frame recording (given {given})")]
+    WrongRecording { given: String },
+    #[error("recording was made with another layout (recorded {given:016x} but configured {actual:016x})")]
+    RecordingLayoutMismatch { given: u64, actual: u64 },
+    #[error("monitor not found (given {given})")]
+    MonitorNotFound { given: String },
     #[error("pipewire error")]
     PipewireError,
 
-    #[error(transparent)]
-    PostfixReading(#[from] io::Error),
     #[error("wrong length (given {given} must be {actual})")]
     WrongLength { given: usize, actual: usize },
+    #[error("too many leds (given {given} must be at most {max})")]
+    TooManyLeds { given: usize, max: usize },
+    #[error("wrong segment (given {start}..{start}+{length} must fit into {actual})")]
+    WrongSegment {
+        start: usize,
+        length: usize,
+        actual: usize,
+    },
+    #[error("skipped led out of range (given {given} must be below {actual})")]
+    SkippedLedOutOfRange { given: usize, actual: usize },
+    #[error("multi strip can not contain another multi strip")]
+    NestedMultiStrip,
+    #[error("no serial device answered with the greeting")]
+    SerialDeviceNotFound,
+
+    #[error("unsupported protocol version (given {given} must be {expected})")]
+    UnsupportedProtocolVersion { given: u8, expected: u8 },
+    #[error("led count mismatch (configured {given} but firmware has {actual})")]
+    LedCountMismatch { given: usize, actual: usize },
+    #[error("frame rejected by the firmware (reason {reason})")]
+    FrameRejected { reason: u8 },
+    #[error("malformed reply from the firmware")]
+    MalformedReply,
+    #[error("no reply from the firmware")]
+    ReplyTimeout,
+
+    #[error("malformed data from the OpenRGB server")]
+    OpenRgbMalformed,
+    #[error("image forwarding needs the Hyperion strip mode and the Screen source")]
+    ImageForwardUnsupported,
+
+    #[error("no OpenRGB zone matches (given controller \"{controller}\" zone \"{zone}\")")]
+    OpenRgbNotFound { controller: String, zone: String },
 }
@@ -1,23 +1,59 @@
+use std::env;
+
 use anyhow::Result;
 use colog::init;
 use log::{error, info};
-use plight::core::strip::Strip;
 
+use plight::cli::Args;
 use plight::config::CONFIG;
-use plight::core::arduino_strip::ArduinoStrip;
-use plight::core::poll;
+use plight::core::frame_record::{FrameRecorder, RecordingHeader};
+use plight::core::hyperion_strip::check_forward;
+use plight::core::multi_strip::MultiStrip;
+use plight::core::recording_strip::RecordingStrip;
+use plight::core::{poll, stop};
 
 fn main() -> Result<()> {
     init();
 
+    let args = Args::parse(env::args())?;
+
     let source_mode = CONFIG.source.mode;
     info!("Current source mode is \"{:?}\"", source_mode);
     let behavior_mode = CONFIG.behavior.mode;
     info!("Current behavior mode is \"{:?}\"", behavior_mode);
 
-    let strip = Box::new(ArduinoStrip::new()?);
+    let strip_mode = CONFIG.strip.mode;
+    info!("Current strip mode is \"{:?}\"", strip_mode);
+
+    // Read once, a layout file is not parsed again by every stage
+    let layout = CONFIG.strip.layout()?;
+    let strip_length = layout.len();
+    info!("Strip has {} LEDs", strip_length);
+
+    check_forward(&CONFIG.strip, source_mode)?;
+
+    let mut strip = strip_mode.get_strip(&CONFIG.strip, &layout, strip_length)?;
+
+    if let Some(path) = &args.record {
+        let recording = RecordingStrip::new(path, &layout, strip_length)?;
+        strip = Box::new(MultiStrip::tee(
+            vec![strip, Box::new(recording)],
+            strip_length,
+        ));
+    }
+
+    let recorder = match &args.record_frames {
+        Some(path) => Some(FrameRecorder::create(
+            path,
+            RecordingHeader::new(&CONFIG.strip, &layout),
+        )?),
+        None => None,
+    };
+
+    // Strips restore the terminal and finish recordings when dropped, which needs poll to return
+    ctrlc::set_handler(stop)?;
 
-    if let Err(e) = poll(strip, source_mode, behavior_mode) {
+    if let Err(e) = poll(strip, layout, source_mode, behavior_mode, recorder) {
         error!("PLight crushed with error: {:}", e);
     }
     Ok(())
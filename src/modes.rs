@@ -1,2 +1,3 @@
 pub mod behaviors;
+pub mod filters;
 pub mod sources;
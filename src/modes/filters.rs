@@ -0,0 +1,67 @@
+pub mod blur;
+pub mod brightness_cap;
+pub mod mirror;
+pub mod rotate;
+pub mod saturation_boost;
+pub mod smoothing;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::led_sequence::LedSequence,
+    modes::filters::{
+        blur::BlurFlt, brightness_cap::BrightnessCapFlt, mirror::MirrorFlt, rotate::RotateFlt,
+        saturation_boost::SaturationBoostFlt, smoothing::SmoothingFlt,
+    },
+};
+
+pub trait Filter {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()>;
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum FilterMod {
+    Smoothing,
+    BrightnessCap,
+    SaturationBoost,
+    Mirror,
+    Rotate,
+    Blur,
+}
+
+impl FilterMod {
+    pub fn get_filter(&self) -> Result<Box<dyn Filter>> {
+        match self {
+            FilterMod::Smoothing => Ok(Box::new(SmoothingFlt::new()?)),
+            FilterMod::BrightnessCap => Ok(Box::new(BrightnessCapFlt::new()?)),
+            FilterMod::SaturationBoost => Ok(Box::new(SaturationBoostFlt::new()?)),
+            FilterMod::Mirror => Ok(Box::new(MirrorFlt::new()?)),
+            FilterMod::Rotate => Ok(Box::new(RotateFlt::new()?)),
+            FilterMod::Blur => Ok(Box::new(BlurFlt::new()?)),
+        }
+    }
+}
+
+/// Filters applied one after another in the configured order
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl FilterChain {
+    pub fn new(chain: &[FilterMod]) -> Result<Self> {
+        let filters = chain
+            .iter()
+            .map(|filter_mod| filter_mod.get_filter())
+            .collect::<Result<_>>()?;
+
+        Ok(FilterChain { filters })
+    }
+
+    pub fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        for filter in &mut self.filters {
+            filter.apply(led_sequence)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::CONFIG;
+use crate::core::led_color::LedColor;
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct BlurFltConf {
+    /// Number of neighbours on each side averaged into every led
+    #[config(default = 1)]
+    pub radius: usize,
+}
+
+/// Box blur along the strip. The strip runs around the screen, so both ends are treated
+/// as neighbours.
+pub struct BlurFlt {}
+
+impl BlurFlt {
+    pub fn new() -> Result<Self> {
+        Ok(BlurFlt {})
+    }
+}
+
+impl Filter for BlurFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        let len = led_sequence.len();
+        let radius = CONFIG.filter.blur.radius.min(len.saturating_sub(1) / 2);
+        if radius == 0 {
+            return Ok(());
+        }
+
+        let colors: Vec<[f32; 3]> = led_sequence.into_iter().map(|c| c.channels()).collect();
+        let window = (2 * radius + 1) as f32;
+
+        for (i, led_color) in led_sequence.into_iter().enumerate() {
+            let mut sum = [0.0; 3];
+            for offset in 0..=2 * radius {
+                let rgb = colors[(i + len + offset - radius) % len];
+                sum.iter_mut().zip(rgb).for_each(|(s, x)| *s += x);
+            }
+            *led_color = LedColor::from(sum.map(|x| x / window));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::CONFIG;
+use crate::core::led_color::LedColor;
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct BrightnessCapFltConf {
+    /// Highest average brightness of the whole strip (1.0 is every led at full white).
+    /// Brighter frames are dimmed evenly, which also limits the power draw.
+    #[config(default = 0.8)]
+    pub max_brightness: f32,
+}
+
+pub struct BrightnessCapFlt {}
+
+impl BrightnessCapFlt {
+    pub fn new() -> Result<Self> {
+        Ok(BrightnessCapFlt {})
+    }
+}
+
+impl Filter for BrightnessCapFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        if led_sequence.is_empty() {
+            return Ok(());
+        }
+
+        let total: f32 = led_sequence
+            .into_iter()
+            .map(|led_color| led_color.channels().iter().sum::<f32>())
+            .sum();
+        let brightness = total / (led_sequence.len() as f32 * 3.0 * 255.0);
+
+        let max_brightness = CONFIG.filter.brightness_cap.max_brightness.max(0.0);
+        if brightness > max_brightness {
+            let scale = max_brightness / brightness;
+            for led_color in led_sequence {
+                *led_color = LedColor::from(led_color.channels().map(|x| x * scale));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct MirrorFltConf {}
+
+/// Reverses the sequence, which mirrors the picture around the strip's start
+pub struct MirrorFlt {}
+
+impl MirrorFlt {
+    pub fn new() -> Result<Self> {
+        Ok(MirrorFlt {})
+    }
+}
+
+impl Filter for MirrorFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        led_sequence.reverse();
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::CONFIG;
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct RotateFltConf {
+    /// Number of leds to shift the colors along the strip (negative shifts backwards)
+    #[config(default = 0)]
+    pub offset: isize,
+}
+
+pub struct RotateFlt {}
+
+impl RotateFlt {
+    pub fn new() -> Result<Self> {
+        Ok(RotateFlt {})
+    }
+}
+
+impl Filter for RotateFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        led_sequence.rotate(CONFIG.filter.rotate.offset);
+        Ok(())
+    }
+}
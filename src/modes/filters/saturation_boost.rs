@@ -0,0 +1,36 @@
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::CONFIG;
+use crate::core::led_color::LedColor;
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct SaturationBoostFltConf {
+    /// Multiplier for the distance of every channel from the color's gray level
+    #[config(default = 1.3)]
+    pub factor: f32,
+}
+
+pub struct SaturationBoostFlt {}
+
+impl SaturationBoostFlt {
+    pub fn new() -> Result<Self> {
+        Ok(SaturationBoostFlt {})
+    }
+}
+
+impl Filter for SaturationBoostFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        let factor = CONFIG.filter.saturation_boost.factor;
+
+        for led_color in led_sequence {
+            let rgb = led_color.channels();
+            let avg = rgb.iter().sum::<f32>() / 3.0;
+            *led_color = LedColor::from(rgb.map(|x| (avg + factor * (x - avg)).clamp(0.0, 255.0)));
+        }
+
+        Ok(())
+    }
+}
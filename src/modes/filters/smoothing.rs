@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use confique::Config;
+
+use crate::config::CONFIG;
+use crate::core::led_sequence::LedSequence;
+use crate::modes::filters::Filter;
+use crate::utils::temporal_smoothing::TemporalSmoother;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct SmoothingFltConf {
+    /// Time constant for leds getting brighter (milliseconds, 0 disables smoothing)
+    #[config(default = 80)]
+    pub rise_ms: u64,
+
+    /// Time constant for leds getting darker (milliseconds, 0 disables smoothing)
+    #[config(default = 250)]
+    pub fall_ms: u64,
+
+    /// Mean change of the whole strip (0.0 - 1.0) treated as a scene cut and shown without
+    /// smoothing
+    #[config(default = 0.35)]
+    pub scene_cut_threshold: f32,
+}
+
+/// Same smoothing the Screen source can do on its own, available to every source
+pub struct SmoothingFlt {
+    smoother: TemporalSmoother,
+}
+
+impl SmoothingFlt {
+    pub fn new() -> Result<Self> {
+        let conf = &CONFIG.filter.smoothing;
+        Ok(SmoothingFlt {
+            smoother: TemporalSmoother::new(
+                Duration::from_millis(conf.rise_ms),
+                Duration::from_millis(conf.fall_ms),
+                conf.scene_cut_threshold,
+            ),
+        })
+    }
+}
+
+impl Filter for SmoothingFlt {
+    fn apply(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        self.smoother.smooth(led_sequence);
+        Ok(())
+    }
+}
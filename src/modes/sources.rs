@@ -1,4 +1,5 @@
 pub mod color;
+pub mod replay;
 pub mod screen;
 pub mod wallpaper;
 
@@ -6,8 +7,10 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    core::led_sequence::LedSequence,
-    modes::sources::{color::ColorSrc, screen::ScreenSrc, wallpaper::WallpaperSrc},
+    core::{layout::Layout, led_sequence::LedSequence},
+    modes::sources::{
+        color::ColorSrc, replay::ReplaySrc, screen::ScreenSrc, wallpaper::WallpaperSrc,
+    },
 };
 
 pub trait Source {
@@ -19,14 +22,16 @@ pub enum SourceMod {
     Color,
     Screen,
     Wallpaper,
+    Replay,
 }
 
 impl SourceMod {
-    pub fn get_source(&self) -> Result<Box<dyn Source>> {
+    pub fn get_source(&self, layout: &Layout) -> Result<Box<dyn Source>> {
         match self {
             SourceMod::Color => Ok(Box::new(ColorSrc::new()?)),
-            SourceMod::Screen => Ok(Box::new(ScreenSrc::new()?)),
-            SourceMod::Wallpaper => Ok(Box::new(WallpaperSrc::new()?)),
+            SourceMod::Screen => Ok(Box::new(ScreenSrc::new(layout)?)),
+            SourceMod::Wallpaper => Ok(Box::new(WallpaperSrc::new(layout)?)),
+            SourceMod::Replay => Ok(Box::new(ReplaySrc::new(layout)?)),
         }
     }
 }
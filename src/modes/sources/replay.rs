@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use confique::Config;
+use log::{info, warn};
+
+use crate::config::CONFIG;
+use crate::core::frame_record::FrameReader;
+use crate::core::layout::Layout;
+use crate::core::led_sequence::LedSequence;
+use crate::errors::PLightError::{RecordingLayoutMismatch, WrongLength};
+use crate::modes::sources::Source;
+
+/// How long to wait between polls once a recording that does not loop has ended
+const FINISHED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
+pub struct ReplaySrcConf {
+    /// Recording made with `--record-frames`
+    #[config(default = "")]
+    pub path: PathBuf,
+
+    /// Playback speed (1.0 is the original speed)
+    #[config(default = 1.0)]
+    pub speed: f64,
+
+    /// Start over at the end of the recording
+    #[config(default = true)]
+    pub looped: bool,
+}
+
+pub struct ReplaySrc {
+    reader: FrameReader,
+    speed: f64,
+    looped: bool,
+    started: Instant,
+    finished: bool,
+    /// Frame read while skipping ahead that is not due yet
+    pending: Option<(Duration, LedSequence)>,
+}
+
+impl ReplaySrc {
+    pub fn new(layout: &Layout) -> Result<Self> {
+        Self::open(&CONFIG.source.replay, layout)
+    }
+
+    fn open(conf: &ReplaySrcConf, layout: &Layout) -> Result<Self> {
+        let reader = FrameReader::open(&conf.path)?;
+        let header = reader.header();
+
+        if header.led_count as usize != layout.len() {
+            return Err(WrongLength {
+                given: header.led_count as usize,
+                actual: layout.len(),
+            }
+            .into());
+        }
+
+        // Same LED count does not mean the LEDs sit in the same places
+        match header.layout_fingerprint {
+            Some(fingerprint) if fingerprint != layout.fingerprint() => {
+                return Err(RecordingLayoutMismatch {
+                    given: fingerprint,
+                    actual: layout.fingerprint(),
+                }
+                .into());
+            }
+            Some(_) => {}
+            None => warn!("Recording does not store its layout, it can not be checked"),
+        }
+
+        info!(
+            "Replaying {} leds recorded on a {}x{} strip with a bottom gap of {}",
+            header.led_count, header.width, header.height, header.bottom_gap
+        );
+
+        Ok(ReplaySrc {
+            reader,
+            speed: conf.speed.max(f64::EPSILON),
+            looped: conf.looped,
+            started: Instant::now(),
+            finished: false,
+            pending: None,
+        })
+    }
+
+    /// Time since the start of the replay at which a frame is shown
+    fn due(&self, timestamp: Duration) -> Duration {
+        timestamp.div_f64(self.speed)
+    }
+}
+
+impl Source for ReplaySrc {
+    fn poll_next(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
+        if self.finished {
+            sleep(FINISHED_POLL_INTERVAL);
+            return Ok(());
+        }
+
+        let next = match self.pending.take() {
+            Some(frame) => Some(frame),
+            None => self.reader.next_frame()?,
+        };
+        let (timestamp, mut frame) = match next {
+            Some(frame) => frame,
+            None if self.looped => {
+                self.reader.rewind()?;
+                self.started = Instant::now();
+                match self.reader.next_frame()? {
+                    Some(frame) => frame,
+                    None => {
+                        self.finished = true;
+                        return Ok(());
+                    }
+                }
+            }
+            None => {
+                info!("Replay finished");
+                self.finished = true;
+                return Ok(());
+            }
+        };
+
+        // Frames are held back until their scaled timestamp
+        if let Some(wait) = self.due(timestamp).checked_sub(self.started.elapsed()) {
+            sleep(wait);
+        }
+
+        // The capture pacer may poll slower than the replay runs, frames that are due by now
+        // are skipped so the speed holds. A loop starts over on the next poll.
+        while let Some((timestamp, next)) = self.reader.next_frame()? {
+            if self.due(timestamp) > self.started.elapsed() {
+                self.pending = Some((timestamp, next));
+                break;
+            }
+            frame = next;
+        }
+
+        led_sequence.set_sequence(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+    use crate::config::StripConf;
+    use crate::core::frame_record::{FrameRecorder, RecordingHeader};
+    use crate::core::layout::LayoutConf;
+    use crate::core::led_color::LedColor;
+
+    const FRAMES: u8 = 6;
+    const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Records frames `FRAME_INTERVAL` apart, every LED of frame `i` has the color `[i, 0, 0]`
+    fn record(name: &str) -> (PathBuf, Layout) {
+        let path = env::temp_dir().join(format!("plight_replay_{}_{}.plfr", name, process::id()));
+        let layout = Layout::new(&LayoutConf::builder().load().unwrap(), 4, 3, 0).unwrap();
+        let header = RecordingHeader::new(&StripConf::builder().load().unwrap(), &layout);
+
+        let mut recorder = FrameRecorder::create(&path, header).unwrap();
+        for i in 0..FRAMES {
+            let led_sequence: LedSequence = (0..layout.len())
+                .map(|_| LedColor::from([i, 0, 0]))
+                .collect();
+            recorder.record(&led_sequence).unwrap();
+            sleep(FRAME_INTERVAL);
+        }
+
+        (path, layout)
+    }
+
+    fn shown(source: &mut ReplaySrc, layout: &Layout) -> u8 {
+        let mut led_sequence = LedSequence::new(layout.len());
+        source.poll_next(&mut led_sequence).unwrap();
+        led_sequence.into_iter().next().unwrap().to_rgb8()[0]
+    }
+
+    #[test]
+    fn slow_polls_skip_to_the_newest_due_frame() {
+        let (path, layout) = record("skip");
+        let conf = ReplaySrcConf {
+            path: path.clone(),
+            speed: 4.0,
+            looped: false,
+        };
+        let mut source = ReplaySrc::open(&conf, &layout).unwrap();
+
+        assert_eq!(shown(&mut source, &layout), 0);
+
+        // Long enough for the whole recording at four times the speed
+        sleep(FRAME_INTERVAL * FRAMES as u32 / 2);
+        assert_eq!(shown(&mut source, &layout), FRAMES - 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn frames_wait_for_their_time() {
+        let (path, layout) = record("wait");
+        let conf = ReplaySrcConf {
+            path: path.clone(),
+            speed: 1.0,
+            looped: true,
+        };
+        let mut source = ReplaySrc::open(&conf, &layout).unwrap();
+
+        let started = Instant::now();
+        let colors: Vec<u8> = (0..FRAMES).map(|_| shown(&mut source, &layout)).collect();
+        assert_eq!(colors, (0..FRAMES).collect::<Vec<_>>());
+        assert!(started.elapsed() >= FRAME_INTERVAL * (FRAMES - 1) as u32 * 9 / 10);
+
+        // Starts over at the end
+        assert_eq!(shown(&mut source, &layout), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,27 +1,93 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use confique::Config;
 use serde::{Deserialize, Serialize};
-use xcap::Monitor;
 
 use crate::config::CONFIG;
+use crate::core::hyperion_strip::{HyperionClient, HyperionForward};
+use crate::core::layout::{Layout, Rect};
 use crate::core::led_sequence::LedSequence;
+use crate::core::strip::StripMod;
 use crate::modes::sources::Source;
+use crate::utils::black_bars::BlackBarDetector;
+use crate::utils::monitors::{capture_stitched, select_monitors, PlacedMonitor};
+use crate::utils::temporal_smoothing::TemporalSmoother;
 use crate::utils::{converters::rgba8_to_rgb8, image_processing::parse_image};
 
 #[derive(Clone, PartialEq, PartialOrd, Debug, Config)]
 pub struct ScreenSrcConf {
     #[config(default = "XCap")]
     pub engine: CaptureEngine,
+
+    /// Monitors to capture by name (e.g. "DP-1") or index, the primary one when empty.
+    /// Several monitors are stitched together as they are arranged on the desktop
+    #[config(default = [])]
+    pub monitors: Vec<String>,
+
+    /// Time constant for leds getting brighter (milliseconds, 0 disables smoothing). The
+    /// Smoothing filter does the same for every source
+    #[config(default = 0)]
+    pub rise_ms: u64,
+
+    /// Time constant for leds getting darker (milliseconds, 0 disables smoothing)
+    #[config(default = 0)]
+    pub fall_ms: u64,
+
+    /// Mean change of the whole strip (0.0 - 1.0) treated as a scene cut and shown without
+    /// smoothing
+    #[config(default = 0.35)]
+    pub scene_cut_threshold: f32,
+
+    /// Detect letterbox and pillarbox bars and sample only the picture between them
+    #[config(default = true)]
+    pub black_bars: bool,
+
+    /// Highest channel value counted as black when looking for bars
+    #[config(default = 16)]
+    pub black_threshold: u8,
+
+    /// Frames thicker bars have to persist before sampling moves inward
+    #[config(default = 30)]
+    pub black_bar_frames: u32,
+
+    /// Manual crop in pixels (top, right, bottom, left), replaces bar detection
+    pub crop: Option<[usize; 4]>,
 }
 
 pub struct ScreenSrc {
-    monitor: Monitor,
+    monitors: Vec<PlacedMonitor>,
+    hyperion: Option<HyperionClient>,
+    layout: Layout,
+    black_bars: Option<BlackBarDetector>,
+    smoother: TemporalSmoother,
 }
 
 impl ScreenSrc {
-    pub fn new() -> Result<Self> {
+    pub fn new(layout: &Layout) -> Result<Self> {
+        let hyperion = if CONFIG.strip.mode == StripMod::Hyperion
+            && CONFIG.strip.hyperion.forward == HyperionForward::Image
+        {
+            Some(HyperionClient::new(&CONFIG.strip.hyperion)?)
+        } else {
+            None
+        };
+
+        let conf = &CONFIG.source.screen;
+        let smoother = TemporalSmoother::new(
+            Duration::from_millis(conf.rise_ms),
+            Duration::from_millis(conf.fall_ms),
+            conf.scene_cut_threshold,
+        );
+
         Ok(ScreenSrc {
-            monitor: Monitor::all()?[0].clone(),
+            monitors: select_monitors(&conf.monitors)?,
+            hyperion,
+            layout: layout.clone(),
+            black_bars: conf
+                .black_bars
+                .then(|| BlackBarDetector::new(conf.black_threshold, conf.black_bar_frames)),
+            smoother,
         })
     }
 }
@@ -29,14 +95,41 @@ impl ScreenSrc {
 impl Source for ScreenSrc {
     fn poll_next(&mut self, led_sequence: &mut LedSequence) -> Result<()> {
         let image = match CONFIG.source.screen.engine {
-            CaptureEngine::XCap => self.monitor.capture_image()?,
+            CaptureEngine::XCap => capture_stitched(&self.monitors)?,
+        };
+
+        let image = rgba8_to_rgb8(image);
+        if let Some(hyperion) = &self.hyperion {
+            hyperion.send_image(&image)?;
+        }
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let area = match (CONFIG.source.screen.crop, &mut self.black_bars) {
+            (Some(crop), _) => crop_area(crop, width, height),
+            (None, Some(black_bars)) => black_bars.update(&image),
+            (None, None) => Rect::full(width, height),
         };
 
-        parse_image(&rgba8_to_rgb8(image), led_sequence);
+        parse_image(&image, area, &self.layout, led_sequence);
+        self.smoother.smooth(led_sequence);
         Ok(())
     }
 }
 
+/// Area left after cutting `[top, right, bottom, left]` pixels off, at least one pixel
+fn crop_area([top, right, bottom, left]: [usize; 4], width: usize, height: usize) -> Rect {
+    let x0 = left.min(width.saturating_sub(1));
+    let y0 = top.min(height.saturating_sub(1));
+
+    Rect {
+        x: (x0, width.saturating_sub(right).clamp(x0 + 1, width.max(1))),
+        y: (
+            y0,
+            height.saturating_sub(bottom).clamp(y0 + 1, height.max(1)),
+        ),
+    }
+}
+
 #[derive(Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
 pub enum CaptureEngine {
     XCap,
@@ -7,6 +7,7 @@ use image::open;
 use serde::{Deserialize, Serialize};
 
 use crate::config::CONFIG;
+use crate::core::layout::{Layout, Rect};
 use crate::core::led_sequence::LedSequence;
 use crate::errors::PLightError::WrongWallpaperPath;
 use crate::modes::sources::Source;
@@ -22,11 +23,12 @@ pub struct WallpaperSrc {
     wallpaper_command: Command,
     prev_output_str: String,
     image_prefix: &'static str,
+    layout: Layout,
     colors: LedSequence,
 }
 
 impl WallpaperSrc {
-    pub fn new() -> Result<Self> {
+    pub fn new(layout: &Layout) -> Result<Self> {
         let wallpaper_command = match CONFIG.source.wallpaper.engine {
             WallpaperEngine::Swww => {
                 let mut command = Command::new("swww");
@@ -36,7 +38,8 @@ impl WallpaperSrc {
         };
 
         let image_prefix = "image: ";
-        let colors = LedSequence::new(CONFIG.strip.len());
+        let layout = layout.clone();
+        let colors = LedSequence::new(layout.len());
 
         let prev_output_str: String = "".into();
 
@@ -44,6 +47,7 @@ impl WallpaperSrc {
             wallpaper_command,
             prev_output_str,
             image_prefix,
+            layout,
             colors,
         })
     }
@@ -68,7 +72,8 @@ impl Source for WallpaperSrc {
 
                 let image = open(image_path)?.into_rgb8();
 
-                parse_image(&image, &mut self.colors);
+                let area = Rect::full(image.width() as usize, image.height() as usize);
+                parse_image(&image, area, &self.layout, &mut self.colors);
 
                 led_sequence.set_sequence(self.colors.clone());
                 Ok(())
@@ -1,4 +1,10 @@
 pub mod audio;
+pub mod black_bars;
+pub mod checksum;
 pub mod color_math;
 pub mod converters;
 pub mod image_processing;
+pub mod latest;
+pub mod monitors;
+pub mod serial;
+pub mod temporal_smoothing;
@@ -0,0 +1,191 @@
+use image::{ImageBuffer, Rgb};
+use log::debug;
+
+use crate::core::layout::Rect;
+
+/// Bars wider than this share of the image are taken for a dark scene, not black bars
+const MAX_BAR_SHARE: usize = 3;
+/// Pixels sampled across every row or column
+const SAMPLES: usize = 64;
+/// Bar changes of up to this many pixels are treated as noise
+const TOLERANCE_P: usize = 4;
+
+/// Finds letterbox and pillarbox bars. Bars are assumed symmetric, so the thinner side of
+/// each pair decides the bar size.
+pub struct BlackBarDetector {
+    threshold: u8,
+    vertical: Hysteresis,
+    horizontal: Hysteresis,
+}
+
+impl BlackBarDetector {
+    /// `threshold` is the highest channel value still counted as black. Thinner bars are
+    /// used right away, thicker bars (a smaller picture area) must persist for `frames`
+    /// frames before they are used
+    pub fn new(threshold: u8, frames: u32) -> Self {
+        BlackBarDetector {
+            threshold,
+            vertical: Hysteresis::new(frames),
+            horizontal: Hysteresis::new(frames),
+        }
+    }
+
+    /// Picture area of the image without the bars
+    pub fn update(&mut self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Rect {
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        if width == 0 || height == 0 {
+            return Rect::full(width, height);
+        }
+
+        let is_black = |x: usize, y: usize| {
+            img.get_pixel(x as u32, y as u32)
+                .0
+                .iter()
+                .all(|&channel| channel <= self.threshold)
+        };
+        let row_is_black = |y: usize| (0..width).step_by(step(width)).all(|x| is_black(x, y));
+        let column_is_black = |x: usize| (0..height).step_by(step(height)).all(|y| is_black(x, y));
+
+        let bar = |length: usize, is_black: &dyn Fn(usize) -> bool| {
+            let limit = length / MAX_BAR_SHARE;
+            let first = (0..limit).take_while(|&i| is_black(i)).count();
+            let last = (0..limit).take_while(|&i| is_black(length - 1 - i)).count();
+            // Reaching the limit means the frame is mostly dark, nothing to decide from
+            (first < limit && last < limit).then_some(first.min(last))
+        };
+
+        let top = self.vertical.update(bar(height, &row_is_black));
+        let left = self.horizontal.update(bar(width, &column_is_black));
+
+        Rect {
+            x: (left, width - left),
+            y: (top, height - top),
+        }
+    }
+}
+
+fn step(length: usize) -> usize {
+    (length / SAMPLES).max(1)
+}
+
+/// Follows bars that get thinner right away, so no picture is cut off, but waits for
+/// thicker bars to hold for a number of frames before using them
+struct Hysteresis {
+    frames: u32,
+    current: usize,
+    candidate: usize,
+    seen: u32,
+}
+
+impl Hysteresis {
+    fn new(frames: u32) -> Self {
+        Hysteresis {
+            frames,
+            current: 0,
+            candidate: 0,
+            seen: 0,
+        }
+    }
+
+    fn update(&mut self, detected: Option<usize>) -> usize {
+        let Some(detected) = detected else {
+            return self.current;
+        };
+
+        if detected < self.current {
+            self.current = detected;
+            self.seen = 0;
+        } else if detected > self.current + TOLERANCE_P {
+            if self.seen > 0 && detected.abs_diff(self.candidate) <= TOLERANCE_P {
+                self.seen += 1;
+            } else {
+                self.candidate = detected;
+                self.seen = 1;
+            }
+
+            if self.seen >= self.frames {
+                debug!("Black bars changed to {} pixels", self.candidate);
+                self.current = self.candidate;
+                self.seen = 0;
+            }
+        } else {
+            self.seen = 0;
+        }
+
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 160;
+    const HEIGHT: u32 = 90;
+
+    /// Picture with black bars of `letterbox` rows at the top and bottom and `pillarbox`
+    /// columns at the left and right
+    fn frame(letterbox: u32, pillarbox: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| {
+            let in_bars =
+                y < letterbox || y >= HEIGHT - letterbox || x < pillarbox || x >= WIDTH - pillarbox;
+            if in_bars {
+                Rgb([3, 3, 3])
+            } else {
+                Rgb([200, 100, 50])
+            }
+        })
+    }
+
+    fn area(letterbox: usize, pillarbox: usize) -> Rect {
+        Rect {
+            x: (pillarbox, WIDTH as usize - pillarbox),
+            y: (letterbox, HEIGHT as usize - letterbox),
+        }
+    }
+
+    #[test]
+    fn thicker_bars_wait_for_frames() {
+        let mut detector = BlackBarDetector::new(10, 3);
+
+        assert_eq!(detector.update(&frame(12, 0)), area(0, 0));
+        assert_eq!(detector.update(&frame(12, 0)), area(0, 0));
+        assert_eq!(detector.update(&frame(12, 0)), area(12, 0));
+
+        // A change within the tolerance is noise
+        for _ in 0..3 {
+            assert_eq!(detector.update(&frame(14, 0)), area(12, 0));
+        }
+
+        // Frames with other bars start the count over
+        detector.update(&frame(12, 20));
+        detector.update(&frame(12, 30));
+        assert_eq!(detector.update(&frame(12, 20)), area(12, 0));
+        detector.update(&frame(12, 20));
+        assert_eq!(detector.update(&frame(12, 20)), area(12, 20));
+    }
+
+    #[test]
+    fn thinner_bars_apply_at_once() {
+        let mut detector = BlackBarDetector::new(10, 2);
+        detector.update(&frame(12, 20));
+        assert_eq!(detector.update(&frame(12, 20)), area(12, 20));
+
+        assert_eq!(detector.update(&frame(11, 5)), area(11, 5));
+        assert_eq!(detector.update(&frame(0, 0)), area(0, 0));
+    }
+
+    #[test]
+    fn dark_frame_keeps_bars() {
+        let mut detector = BlackBarDetector::new(10, 1);
+        let dark = frame(HEIGHT / 2, 0);
+
+        assert_eq!(detector.update(&dark), area(0, 0));
+
+        assert_eq!(detector.update(&frame(12, 0)), area(12, 0));
+        assert_eq!(detector.update(&dark), area(12, 0));
+
+        // Bars reaching a third of the image count as a dark scene too
+        assert_eq!(detector.update(&frame(HEIGHT / 3, 0)), area(12, 0));
+    }
+}
@@ -23,3 +23,54 @@ pub fn rgba8_to_rgb8(
 
     image::ImageBuffer::from_raw(width as u32, height as u32, output_data).unwrap()
 }
+
+pub fn bytes_to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+
+        for (input, encoded) in vectors {
+            assert_eq!(bytes_to_base64(input.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn base64_uses_whole_alphabet() {
+        assert_eq!(bytes_to_base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(bytes_to_base64(&[0, 0, 0]), "AAAA");
+    }
+}
@@ -2,80 +2,161 @@ use std::time::Instant;
 
 use image::{ImageBuffer, Rgb};
 use log::trace;
-use ndarray::{s, Array2};
 
-use crate::config::CONFIG;
+use crate::config::{ParseMode, CONFIG};
+use crate::core::layout::{Layout, Rect};
+use crate::core::led_color::LedColor;
 use crate::core::led_sequence::LedSequence;
-use crate::utils::color_math::{average, median};
+use crate::utils::color_math::median;
 
-// TODO: Replace Vec with &[Rgb<u8>]>
-pub fn parse_image(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, led_sequence: &mut LedSequence) {
+/// Samples the colors of every LED from `area` of the image, the active picture without
+/// black bars
+pub fn parse_image(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    area: Rect,
+    layout: &Layout,
+    led_sequence: &mut LedSequence,
+) {
     let __debug_time = Instant::now();
 
-    let process = match CONFIG.global.parse_mode {
-        crate::config::ParseMode::Average => average,
-        crate::config::ParseMode::Median => median,
+    let rects = layout.sample_rects(area, CONFIG.strip.corner_size_p, CONFIG.strip.thickness_p);
+
+    let factor = CONFIG.global.downscale;
+    let colors = if factor > 1 {
+        let small = downscale(img, factor);
+        let (width, height) = (small.width() as usize, small.height() as usize);
+        let rects: Vec<_> = rects
+            .iter()
+            .map(|rect| rect.map(|rect| shrink(rect, factor, width, height)))
+            .collect();
+        sample_colors(
+            &small,
+            &rects,
+            &CONFIG.global.parse_mode,
+            CONFIG.global.sample_step,
+        )
+    } else {
+        sample_colors(
+            img,
+            &rects,
+            &CONFIG.global.parse_mode,
+            CONFIG.global.sample_step,
+        )
     };
 
-    let (width_p, height_p) = (img.width() as usize, img.height() as usize);
-    let horizontal_thickness_p = (width_p - CONFIG.strip.corner_size_p * 2) / CONFIG.strip.width;
-    let vertical_thickness_p = (height_p - CONFIG.strip.corner_size_p * 2) / CONFIG.strip.height;
-    let half_bottom_length = (CONFIG.strip.width - CONFIG.strip.bottom_gap) / 2;
-
-    let pixels: Array2<Rgb<u8>> = Array2::from_shape_fn((height_p, width_p), |(y, x)| {
-        *img.get_pixel(x as u32, y as u32)
-    });
-
-    let mut colors = Vec::with_capacity(CONFIG.strip.len());
-
-    // Bottom right
-    let right_bottom_offset_p =
-        CONFIG.strip.corner_size_p + (half_bottom_length * horizontal_thickness_p);
-    for i in (0..half_bottom_length).rev() {
-        let slice = pixels.slice(s![
-            (height_p - CONFIG.strip.thickness_p)..height_p,
-            (right_bottom_offset_p + (i * horizontal_thickness_p))
-                ..(right_bottom_offset_p + ((i + 1) * horizontal_thickness_p))
-        ]);
-        colors.push(process(&slice.iter().copied().collect::<Vec<_>>()));
-    }
+    led_sequence.set_colors(&colors);
+    trace!("Image processing duration: {:?}", __debug_time.elapsed());
+}
 
-    // Right
-    for i in (0..CONFIG.strip.height).rev() {
-        let slice = pixels.slice(s![
-            (i * vertical_thickness_p)..((i + 1) * vertical_thickness_p),
-            (width_p - CONFIG.strip.thickness_p)..width_p
-        ]);
-        colors.push(process(&slice.iter().copied().collect::<Vec<_>>()));
-    }
+/// Color of every rectangle, read straight from the image buffer. Only every `step`-th
+/// pixel of every `step`-th row is sampled, rectangles must lie within the image.
+pub fn sample_colors(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    rects: &[Option<Rect>],
+    parse_mode: &ParseMode,
+    step: usize,
+) -> Vec<LedColor> {
+    let step = step.max(1);
+    let width = img.width() as usize;
+    let raw = img.as_raw();
 
-    // Top
-    for i in (0..CONFIG.strip.width).rev() {
-        let slice = pixels.slice(s![
-            0..CONFIG.strip.thickness_p,
-            (i * horizontal_thickness_p)..((i + 1) * horizontal_thickness_p)
-        ]);
-        colors.push(process(&slice.iter().copied().collect::<Vec<_>>()));
-    }
+    // Rows of a rectangle as raw RGB bytes
+    let rows = move |rect: Rect| {
+        (rect.y.0..rect.y.1)
+            .step_by(step)
+            .map(move |y| &raw[(y * width + rect.x.0) * 3..(y * width + rect.x.1) * 3])
+    };
 
-    // Left
-    for i in 0..CONFIG.strip.height {
-        let slice = pixels.slice(s![
-            (i * vertical_thickness_p)..((i + 1) * vertical_thickness_p),
-            0..CONFIG.strip.thickness_p,
-        ]);
-        colors.push(process(&slice.iter().copied().collect::<Vec<_>>()));
-    }
+    let mut pixels = Vec::new();
+    rects
+        .iter()
+        .map(|rect| {
+            // Skipped LEDs stay dark
+            let Some(rect) = *rect else {
+                return LedColor::default();
+            };
+
+            match parse_mode {
+                ParseMode::Average => {
+                    let mut total = [0_u64; 3];
+                    let mut count = 0_u64;
+                    for row in rows(rect) {
+                        for pixel in row.chunks_exact(3).step_by(step) {
+                            total[0] += pixel[0] as u64;
+                            total[1] += pixel[1] as u64;
+                            total[2] += pixel[2] as u64;
+                            count += 1;
+                        }
+                    }
+                    LedColor::from(total.map(|total| (total / count.max(1)) as f32))
+                }
+                ParseMode::Median => {
+                    pixels.clear();
+                    for row in rows(rect) {
+                        pixels.extend(
+                            row.chunks_exact(3)
+                                .step_by(step)
+                                .map(|pixel| Rgb([pixel[0], pixel[1], pixel[2]])),
+                        );
+                    }
+                    median(&pixels)
+                }
+            }
+        })
+        .collect()
+}
 
-    // Bottom left
-    for i in 0..half_bottom_length {
-        let slice = pixels.slice(s![
-            (height_p - CONFIG.strip.thickness_p)..height_p,
-            (i * horizontal_thickness_p)..((i + 1) * horizontal_thickness_p)
-        ]);
-        colors.push(process(&slice.iter().copied().collect::<Vec<_>>()));
+/// Averages `factor x factor` blocks into single pixels. Pixels left over at the right and
+/// bottom edges are dropped.
+pub fn downscale(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    factor: usize,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let factor = factor.max(1);
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (small_width, small_height) = ((width / factor).max(1), (height / factor).max(1));
+    let raw = img.as_raw();
+
+    let mut small = Vec::with_capacity(small_width * small_height * 3);
+    let mut totals = vec![0_u32; small_width * 3];
+    for block_y in 0..small_height {
+        totals.iter_mut().for_each(|total| *total = 0);
+
+        let rows = (block_y * factor)..((block_y + 1) * factor).min(height);
+        let block_height = rows.len();
+        let block_width = factor.min(width);
+        for y in rows {
+            let row = &raw[y * width * 3..(y + 1) * width * 3];
+            for (block, total) in row.chunks(block_width * 3).zip(totals.chunks_exact_mut(3)) {
+                let (mut r, mut g, mut b) = (0_u32, 0_u32, 0_u32);
+                for pixel in block.chunks_exact(3) {
+                    r += pixel[0] as u32;
+                    g += pixel[1] as u32;
+                    b += pixel[2] as u32;
+                }
+                total[0] += r;
+                total[1] += g;
+                total[2] += b;
+            }
+        }
+
+        let pixels = (block_width * block_height) as u32;
+        small.extend(totals.iter().map(|total| (total / pixels) as u8));
     }
 
-    led_sequence.set_colors(&colors);
-    trace!("Image processing duration: {:?}", __debug_time.elapsed());
+    ImageBuffer::from_raw(small_width as u32, small_height as u32, small)
+        .expect("buffer matches the downscaled size")
+}
+
+/// `rect` in the coordinates of an image downscaled by `factor`, at least one pixel
+fn shrink(rect: Rect, factor: usize, width: usize, height: usize) -> Rect {
+    let scale = |(start, end): (usize, usize), length: usize| {
+        let start = (start / factor).min(length - 1);
+        (start, (end / factor).clamp(start + 1, length))
+    };
+
+    Rect {
+        x: scale(rect.x, width),
+        y: scale(rect.y, height),
+    }
 }
@@ -0,0 +1,88 @@
+//! Single-slot channel where a new value replaces the one not yet received, so a slow
+//! consumer always gets the latest frame instead of a growing backlog.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+struct Slot<T> {
+    value: Option<T>,
+    closed: bool,
+}
+
+struct Shared<T> {
+    slot: Mutex<Slot<T>>,
+    ready: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn close(&self) {
+        self.slot.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+}
+
+pub struct LatestSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct LatestReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub fn latest_channel<T>() -> (LatestSender<T>, LatestReceiver<T>) {
+    let shared = Arc::new(Shared {
+        slot: Mutex::new(Slot {
+            value: None,
+            closed: false,
+        }),
+        ready: Condvar::new(),
+    });
+
+    (
+        LatestSender {
+            shared: shared.clone(),
+        },
+        LatestReceiver { shared },
+    )
+}
+
+impl<T> LatestSender<T> {
+    /// Replaces any value still waiting. Returns `false` once the receiver is gone.
+    pub fn send(&self, value: T) -> bool {
+        let mut slot = self.shared.slot.lock().unwrap();
+        if slot.closed {
+            return false;
+        }
+
+        slot.value = Some(value);
+        self.shared.ready.notify_one();
+        true
+    }
+}
+
+impl<T> LatestReceiver<T> {
+    /// Blocks until a value arrives. Returns `None` once the sender is gone.
+    pub fn recv(&self) -> Option<T> {
+        let mut slot = self.shared.slot.lock().unwrap();
+        loop {
+            if let Some(value) = slot.value.take() {
+                return Some(value);
+            }
+            if slot.closed {
+                return None;
+            }
+            slot = self.shared.ready.wait(slot).unwrap();
+        }
+    }
+}
+
+impl<T> Drop for LatestSender<T> {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+impl<T> Drop for LatestReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use log::info;
+use xcap::Monitor;
+
+use crate::errors::PLightError::MonitorNotFound;
+
+/// Monitor with its place on the desktop, in logical pixels
+pub struct PlacedMonitor {
+    monitor: Monitor,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// Finds monitors by name or index. No selectors pick the primary monitor.
+pub fn select_monitors(selectors: &[String]) -> Result<Vec<PlacedMonitor>> {
+    let monitors = Monitor::all()?;
+
+    for (index, monitor) in monitors.iter().enumerate() {
+        info!(
+            "Monitor {}: \"{}\" {}x{} at {}, {}",
+            index,
+            monitor.name()?,
+            monitor.width()?,
+            monitor.height()?,
+            monitor.x()?,
+            monitor.y()?
+        );
+    }
+
+    let selected = if selectors.is_empty() {
+        let mut primary = None;
+        for monitor in &monitors {
+            if monitor.is_primary()? {
+                primary = Some(monitor.clone());
+                break;
+            }
+        }
+        let primary = primary.or_else(|| monitors.first().cloned());
+        vec![primary.ok_or(MonitorNotFound {
+            given: "primary".into(),
+        })?]
+    } else {
+        let mut selected = Vec::with_capacity(selectors.len());
+        for selector in selectors {
+            let by_index = selector.parse::<usize>().ok().and_then(|i| monitors.get(i));
+            let by_name = || -> Result<Option<&Monitor>> {
+                for monitor in &monitors {
+                    if monitor.name()? == *selector {
+                        return Ok(Some(monitor));
+                    }
+                }
+                Ok(None)
+            };
+
+            let monitor = match by_index {
+                Some(monitor) => monitor,
+                None => by_name()?.ok_or(MonitorNotFound {
+                    given: selector.clone(),
+                })?,
+            };
+            selected.push(monitor.clone());
+        }
+        selected
+    };
+
+    selected
+        .into_iter()
+        .map(|monitor| {
+            Ok(PlacedMonitor {
+                x: monitor.x()?,
+                y: monitor.y()?,
+                width: monitor.width()?,
+                height: monitor.height()?,
+                monitor,
+            })
+        })
+        .collect()
+}
+
+/// Captures every monitor and puts the images together as they are arranged on the
+/// desktop. Areas no monitor covers stay black.
+pub fn capture_stitched(monitors: &[PlacedMonitor]) -> Result<RgbaImage> {
+    if let [placed] = monitors {
+        return Ok(placed.monitor.capture_image()?);
+    }
+
+    let images = monitors
+        .iter()
+        .map(|placed| Ok(placed.monitor.capture_image()?))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Captures may be larger than the logical size on scaled monitors
+    let scale = monitors
+        .iter()
+        .zip(&images)
+        .map(|(placed, image)| image.width() as f64 / placed.width.max(1) as f64)
+        .fold(1.0, f64::max);
+
+    let left = monitors.iter().map(|placed| placed.x).min().unwrap_or(0);
+    let top = monitors.iter().map(|placed| placed.y).min().unwrap_or(0);
+    let right = monitors
+        .iter()
+        .map(|placed| placed.x + placed.width as i32)
+        .max()
+        .unwrap_or(0);
+    let bottom = monitors
+        .iter()
+        .map(|placed| placed.y + placed.height as i32)
+        .max()
+        .unwrap_or(0);
+
+    let scaled = |value: i32| (value as f64 * scale).round() as u32;
+    let mut canvas = RgbaImage::new(scaled(right - left), scaled(bottom - top));
+
+    for (placed, image) in monitors.iter().zip(images) {
+        let (width, height) = (scaled(placed.width as i32), scaled(placed.height as i32));
+        let image = if image.dimensions() == (width, height) {
+            image
+        } else {
+            imageops::resize(&image, width, height, FilterType::Nearest)
+        };
+
+        imageops::replace(
+            &mut canvas,
+            &image,
+            scaled(placed.x - left) as i64,
+            scaled(placed.y - top) as i64,
+        );
+    }
+
+    Ok(canvas)
+}
@@ -0,0 +1,70 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serialport::{available_ports, SerialPort, SerialPortType};
+
+/// Narrows serial device discovery down to matching USB devices, unset fields match anything
+#[derive(Clone, Debug, Default)]
+pub struct UsbFilter<'a> {
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<&'a str>,
+}
+
+impl UsbFilter<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.vid.is_none() && self.pid.is_none() && self.serial_number.is_none()
+    }
+}
+
+/// Lists paths of the USB serial devices that pass the filter
+pub fn usb_ports(filter: &UsbFilter) -> Result<Vec<String>> {
+    Ok(available_ports()?
+        .into_iter()
+        .filter(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => {
+                filter.vid.is_none_or(|vid| vid == info.vid)
+                    && filter.pid.is_none_or(|pid| pid == info.pid)
+                    && filter
+                        .serial_number
+                        .is_none_or(|sn| info.serial_number.as_deref() == Some(sn))
+            }
+            _ => false,
+        })
+        .map(|port| port.port_name)
+        .collect())
+}
+
+/// Reads from the port until `greeting` shows up or `timeout` passes
+pub fn wait_for_greeting(
+    port: &mut Box<dyn SerialPort + Send>,
+    greeting: &[u8],
+    timeout: Duration,
+) -> io::Result<bool> {
+    let deadline = Instant::now() + timeout;
+    let port_timeout = port.timeout();
+    port.set_timeout(Duration::from_millis(100))?;
+
+    let mut window = Vec::with_capacity(greeting.len());
+    let mut byte = [0; 1];
+    let mut found = false;
+
+    while !found && Instant::now() < deadline {
+        match port.read(&mut byte) {
+            Ok(0) => {}
+            Ok(_) => {
+                if window.len() == greeting.len() {
+                    window.remove(0);
+                }
+                window.push(byte[0]);
+                found = window == greeting;
+            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    port.set_timeout(port_timeout)?;
+    Ok(found)
+}
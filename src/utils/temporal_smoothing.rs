@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::core::led_color::LedColor;
+use crate::core::led_sequence::LedSequence;
+
+/// Per-led exponential smoothing with separate time constants for getting brighter and
+/// darker. Time constants are in real time, so the result does not depend on frame rate.
+pub struct TemporalSmoother {
+    rise: Duration,
+    fall: Duration,
+    scene_cut_threshold: f32,
+    previous: Option<(LedSequence, Instant)>,
+}
+
+impl TemporalSmoother {
+    pub fn new(rise: Duration, fall: Duration, scene_cut_threshold: f32) -> Self {
+        TemporalSmoother {
+            rise,
+            fall,
+            scene_cut_threshold,
+            previous: None,
+        }
+    }
+
+    pub fn smooth(&mut self, led_sequence: &mut LedSequence) {
+        let now = Instant::now();
+
+        if let Some((previous, at)) = &self.previous {
+            if previous.len() == led_sequence.len() && !self.is_scene_cut(previous, led_sequence) {
+                let elapsed = now.duration_since(*at).as_secs_f32();
+                let rise = mix_factor(elapsed, self.rise);
+                let fall = mix_factor(elapsed, self.fall);
+
+                for (led_color, previous) in led_sequence.into_iter().zip(previous) {
+                    let current = led_color.channels();
+                    let previous = previous.channels();
+                    let factor = if current.iter().sum::<f32>() > previous.iter().sum::<f32>() {
+                        rise
+                    } else {
+                        fall
+                    };
+
+                    *led_color = LedColor::from(
+                        [0, 1, 2].map(|i| previous[i] + (current[i] - previous[i]) * factor),
+                    );
+                }
+            }
+        }
+
+        self.previous = Some((led_sequence.clone(), now));
+    }
+
+    /// A scene cut is a change of the mean channel difference over the whole strip above
+    /// the threshold (as a share of the full range). Those frames are shown right away.
+    fn is_scene_cut(&self, previous: &LedSequence, current: &LedSequence) -> bool {
+        if current.is_empty() {
+            return false;
+        }
+
+        let difference: f32 = previous
+            .into_iter()
+            .zip(current)
+            .map(|(previous, current)| {
+                let previous = previous.channels();
+                let current = current.channels();
+                (0..3)
+                    .map(|i| (current[i] - previous[i]).abs())
+                    .sum::<f32>()
+            })
+            .sum();
+        let difference = difference / (current.len() as f32 * 3.0 * 255.0);
+
+        let is_cut = difference > self.scene_cut_threshold;
+        if is_cut {
+            debug!("Scene cut detected ({:.2} change)", difference);
+        }
+        is_cut
+    }
+}
+
+/// Share of the new value to take after `elapsed` seconds for a time constant `tau`
+fn mix_factor(elapsed: f32, tau: Duration) -> f32 {
+    if tau.is_zero() {
+        return 1.0;
+    }
+    1.0 - (-elapsed / tau.as_secs_f32()).exp()
+}